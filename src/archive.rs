@@ -0,0 +1,390 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Export of the data a node keeps on disk to a single archive, and import of it, e.g. when moving
+//! the node to new hardware.
+
+use crate::{
+    chunk_store::{reconcile_used_space, UsedSpaceReport, USED_SPACE_FILENAME},
+    node::migrate_dirs,
+    Config, Error, Result,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Builder, Header};
+use tiny_keccak::Keccak;
+
+/// Version of the archive format written by `export_archive`.
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Path of the manifest, which is the first entry of an archive.
+const MANIFEST_PATH: &str = "manifest.json";
+
+/// Directory within an archive holding the contents of the root dir.
+const ROOT_DIR_PREFIX: &str = "root";
+
+/// Directory within an archive holding the contents of each data dir, by index.
+const DATA_DIR_PREFIX: &str = "data";
+
+/// Describes the contents of an archive.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct ArchiveManifest {
+    /// Version of the archive format.
+    pub version: u32,
+    /// Version of the node that exported it.
+    pub node_version: String,
+    /// The archived files, by their path within the archive.
+    pub files: BTreeMap<String, ArchivedFile>,
+}
+
+/// A file within an archive.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct ArchivedFile {
+    /// Size in bytes.
+    pub size: u64,
+    /// Hex encoded SHA3-256 hash of the contents.
+    pub checksum: String,
+}
+
+impl Display for ArchiveManifest {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "archive version {} from sn_node {}: {} files, {} bytes",
+            self.version,
+            self.node_version,
+            self.files.len(),
+            self.files.values().map(|file| file.size).sum::<u64>()
+        )
+    }
+}
+
+/// Writes the contents of the root dir and data dirs of `config` (i.e. the chunk stores, metadata
+/// and transfer dbs, age group and keys) to an archive at `to`.  The used space records aren't
+/// included, as they are rebuilt on import.
+///
+/// The node is expected not to be running, so that the contents don't change meanwhile.
+pub fn export_archive(config: &Config, to: &Path) -> Result<ArchiveManifest> {
+    let data_dirs: Vec<PathBuf> = config
+        .data_dirs()
+        .iter()
+        .map(|data_dir| data_dir.path.clone())
+        .collect();
+    let root_dir = config.root_dir()?;
+    let mut sources = vec![(ROOT_DIR_PREFIX.to_string(), root_dir.clone())];
+    for (index, data_dir) in data_dirs.iter().enumerate() {
+        if *data_dir == root_dir {
+            continue;
+        }
+        sources.push((format!("{}/{}", DATA_DIR_PREFIX, index), data_dir.clone()));
+    }
+
+    let mut files = Vec::new();
+    for (prefix, dir) in &sources {
+        // Data dirs within the root dir are archived on their own.
+        let excluded: Vec<&PathBuf> = data_dirs.iter().filter(|path| *path != dir).collect();
+        for path in list_files(dir, &excluded)? {
+            if path.file_stem().and_then(|stem| stem.to_str()) == Some(USED_SPACE_FILENAME) {
+                continue;
+            }
+            let relative = path.strip_prefix(dir).map_err(|_| {
+                Error::Logic(format!(
+                    "{} is not within {}",
+                    path.display(),
+                    dir.display()
+                ))
+            })?;
+            files.push((archive_path(prefix, relative)?, path));
+        }
+    }
+
+    let mut manifest = ArchiveManifest {
+        version: ARCHIVE_VERSION,
+        node_version: env!("CARGO_PKG_VERSION").to_string(),
+        files: BTreeMap::new(),
+    };
+    for (archive_path, path) in &files {
+        let file = hash_contents(File::open(path)?)?;
+        let _ = manifest.files.insert(archive_path.clone(), file);
+    }
+
+    let mut builder = Builder::new(File::create(to)?);
+    let manifest_contents = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = Header::new_gnu();
+    header.set_size(manifest_contents.len() as u64);
+    header.set_mode(0o600);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_PATH, manifest_contents.as_slice())?;
+    for (archive_path, path) in &files {
+        builder.append_path_with_name(path, archive_path)?;
+    }
+    builder.into_inner()?.sync_all()?;
+
+    info!("Exported {} to {}", manifest, to.display());
+    Ok(manifest)
+}
+
+/// Restores the contents of the archive at `from` to the root dir and data dirs of `config`, which
+/// must be empty, migrates them to the current format, and rebuilds the used space records of the
+/// chunk stores.  Chunks from data dirs beyond those configured are restored to the root dir.
+///
+/// The whole archive is checked against its manifest before anything is written.
+pub async fn import_archive(
+    config: &Config,
+    from: &Path,
+) -> Result<(ArchiveManifest, Vec<UsedSpaceReport>)> {
+    let manifest = validate_archive(from)?;
+
+    let root_dir = config.root_dir()?;
+    let data_dirs: Vec<PathBuf> = config
+        .data_dirs()
+        .iter()
+        .map(|data_dir| data_dir.path.clone())
+        .collect();
+    for dir in std::iter::once(&root_dir).chain(&data_dirs) {
+        if fs::read_dir(dir)
+            .ok()
+            .and_then(|mut entries| entries.next())
+            .is_some()
+        {
+            return Err(Error::Logic(format!(
+                "Can't import into {}, as it isn't empty",
+                dir.display()
+            )));
+        }
+    }
+
+    let mut archive = Archive::new(File::open(from)?);
+    for entry in archive.entries()?.skip(1) {
+        let mut entry = entry?;
+        let path = destination(&entry_path(&entry)?, &root_dir, &data_dirs)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _ = entry.unpack(&path)?;
+    }
+    info!("Imported {} from {}", manifest, from.display());
+    // The archive may have been exported by an older node.
+    migrate_dirs(config).await?;
+
+    let reports = reconcile_used_space(
+        &config.chunk_dirs()?,
+        config.chunk_store_backend(),
+        config.chunk_dir_depth(),
+    )
+    .await?;
+    Ok((manifest, reports))
+}
+
+/// Reads the manifest of the archive at `from`, and checks that the archive holds exactly the
+/// files it lists, with the listed contents.
+fn validate_archive(from: &Path) -> Result<ArchiveManifest> {
+    let mut archive = Archive::new(File::open(from)?);
+    let mut entries = archive.entries()?;
+    let manifest: ArchiveManifest = match entries.next() {
+        Some(entry) => {
+            let entry = entry?;
+            if entry_path(&entry)? != MANIFEST_PATH {
+                return Err(Error::InvalidArchive("missing manifest".to_string()));
+            }
+            serde_json::from_reader(entry)?
+        }
+        None => return Err(Error::InvalidArchive("empty archive".to_string())),
+    };
+    if manifest.version != ARCHIVE_VERSION {
+        return Err(Error::InvalidArchive(format!(
+            "unsupported version {}",
+            manifest.version
+        )));
+    }
+
+    let mut unseen: Vec<&String> = manifest.files.keys().collect();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry_path(&entry)?;
+        // Also rejects paths which could escape the destination dirs.
+        let _ = destination(&path, Path::new(""), &[])?;
+        let expected = manifest
+            .files
+            .get(&path)
+            .ok_or_else(|| Error::InvalidArchive(format!("unlisted file {}", path)))?;
+        if hash_contents(entry)? != *expected {
+            return Err(Error::InvalidArchive(format!(
+                "checksum mismatch for {}",
+                path
+            )));
+        }
+        unseen.retain(|listed| **listed != path);
+    }
+    match unseen.first() {
+        Some(path) => Err(Error::InvalidArchive(format!("missing file {}", path))),
+        None => Ok(manifest),
+    }
+}
+
+/// Lists all files below `dir`, other than those below `excluded` dirs.
+fn list_files(dir: &Path, excluded: &[&PathBuf]) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            if !excluded.iter().any(|excluded| **excluded == path) {
+                files.extend(list_files(&path, excluded)?);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Path within an archive of the file at `relative` within the dir archived under `prefix`.
+fn archive_path(prefix: &str, relative: &Path) -> Result<String> {
+    let mut path = prefix.to_string();
+    for component in relative.components() {
+        let name = component
+            .as_os_str()
+            .to_str()
+            .ok_or_else(|| Error::Logic(format!("Non UTF-8 path {}", relative.display())))?;
+        path.push('/');
+        path.push_str(name);
+    }
+    Ok(path)
+}
+
+/// Path an archived file is restored to.
+fn destination(archive_path: &str, root_dir: &Path, data_dirs: &[PathBuf]) -> Result<PathBuf> {
+    let invalid = || Error::InvalidArchive(format!("invalid path {}", archive_path));
+    let mut parts = archive_path.splitn(2, '/');
+    let (dir, relative) = match (parts.next(), parts.next()) {
+        (Some(ROOT_DIR_PREFIX), Some(relative)) => (root_dir, relative),
+        (Some(DATA_DIR_PREFIX), Some(rest)) => {
+            let mut parts = rest.splitn(2, '/');
+            let index: usize = parts
+                .next()
+                .and_then(|index| index.parse().ok())
+                .ok_or_else(invalid)?;
+            let relative = parts.next().ok_or_else(invalid)?;
+            let dir = data_dirs.get(index).map_or(root_dir, PathBuf::as_path);
+            (dir, relative)
+        }
+        _ => return Err(invalid()),
+    };
+    let relative = Path::new(relative);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(invalid());
+    }
+    Ok(dir.join(relative))
+}
+
+fn entry_path<R: Read>(entry: &tar::Entry<R>) -> Result<String> {
+    Ok(entry.path()?.to_string_lossy().into_owned())
+}
+
+fn hash_contents<R: Read>(mut reader: R) -> Result<ArchivedFile> {
+    let mut hasher = Keccak::new_sha3_256();
+    let mut buffer = [0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    let mut hash = [0; 32];
+    hasher.finalize(&mut hash);
+    Ok(ArchivedFile {
+        size,
+        checksum: hex::encode(hash),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_archive, import_archive};
+    use crate::{
+        chunk_store::{BackendOptions, BlobChunkStore, UsedSpace},
+        Config, Error, Result,
+    };
+    use sn_data_types::{Blob, PublicBlob};
+    use std::fs;
+    use tempdir::TempDir;
+
+    fn options(config: &Config) -> BackendOptions {
+        BackendOptions {
+            kind: config.chunk_store_backend(),
+            dir_depth: config.chunk_dir_depth(),
+        }
+    }
+
+    #[tokio::test]
+    async fn exported_archive_is_imported() -> Result<()> {
+        let source = TempDir::new("export")?;
+        let mut config = Config::default();
+        config.set_root_dir(source.path());
+        let mut chunk_store =
+            BlobChunkStore::new(&[source.path()], options(&config), UsedSpace::new(u64::MAX))
+                .await?;
+        let blob = Blob::Public(PublicBlob::new(vec![1; 100]));
+        chunk_store.put(&blob).await?;
+        drop(chunk_store);
+        fs::write(source.path().join("age_group"), b"adult")?;
+
+        let archive_dir = TempDir::new("archive")?;
+        let archive_path = archive_dir.path().join("node.tar");
+        let exported = export_archive(&config, &archive_path)?;
+
+        let target = TempDir::new("import")?;
+        config.set_root_dir(target.path());
+        let (imported, reports) = import_archive(&config, &archive_path).await?;
+        assert_eq!(exported, imported);
+        assert_eq!(
+            b"adult".to_vec(),
+            fs::read(target.path().join("age_group"))?
+        );
+        assert_eq!(1, reports.len());
+        assert_eq!(1, reports[0].chunk_count);
+        let chunk_store =
+            BlobChunkStore::new(&[target.path()], options(&config), UsedSpace::new(u64::MAX))
+                .await?;
+        assert_eq!(blob, chunk_store.get(blob.address()).await?);
+
+        // Not into a dir which isn't empty.
+        assert!(import_archive(&config, &archive_path).await.is_err());
+
+        // Nor from a corrupted archive.
+        let mut contents = fs::read(&archive_path)?;
+        let last = contents.iter().rposition(|byte| *byte != 0).unwrap_or(0);
+        contents[last] ^= 1;
+        fs::write(&archive_path, &contents)?;
+        let target = TempDir::new("import")?;
+        config.set_root_dir(target.path());
+        assert!(matches!(
+            import_archive(&config, &archive_path).await,
+            Err(Error::InvalidArchive(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! sn_economics sweeps the parameters of the network economics (the cost of storing data, the
+//! rewards of nodes and the token supply of sections) over ranges, and prints the resulting tables
+//! as CSV or JSON.
+
+#![forbid(unsafe_code)]
+#![warn(
+    missing_debug_implementations,
+    missing_docs,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications,
+    unused_results
+)]
+
+use serde_json::{Map, Value};
+use sn_data_types::Token;
+use sn_node::economics::{self, Pricing};
+use std::{convert::TryFrom, str::FromStr};
+use structopt::StructOpt;
+
+/// The oldest age the reward of which can be expressed in nanos.
+const MAX_REWARDED_AGE: u8 = 34;
+
+/// The longest section prefix, i.e. the length of the names it is a prefix of, in bits.
+const MAX_PREFIX_LEN: u64 = 256;
+
+/// Sweeps the parameters of the network economics, printing the resulting tables.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case", bin_name = "sn_economics")]
+struct Args {
+    /// Format of the printed table: "csv" or "json".
+    #[structopt(long, default_value = "csv")]
+    format: Format,
+    #[structopt(subcommand)]
+    table: Table,
+}
+
+/// The tables that can be printed.
+///
+/// Parameters take comma separated values and ranges, e.g. "0,4-8,1024-4096:1024", where
+/// "START-END:STEP" steps from START to END inclusive, by 1 if no STEP is given.
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum Table {
+    /// The cost of storing data, by pricing policy.
+    StoreCost {
+        /// Sizes of the data stored, in bytes.
+        #[structopt(long, default_value = "1024,1048576")]
+        bytes: Sweep,
+        /// Numbers of adults in the section.
+        #[structopt(long, default_value = "8")]
+        adults: Sweep,
+        /// Numbers of full adults in the section. Those above the number of adults are skipped.
+        #[structopt(long, default_value = "0-7")]
+        full_adults: Sweep,
+        /// Lengths of the section prefix, in bits.
+        #[structopt(long, default_value = "0")]
+        prefix_len: Sweep,
+        /// Pricing policies to compare: "curve" or "linear". If not given, all of them.
+        #[structopt(long, number_of_values = 1)]
+        pricing: Vec<Pricing>,
+    },
+    /// The rewards of nodes.
+    Reward {
+        /// Ages of the rewarded node. Those above 34 are skipped.
+        #[structopt(long, default_value = "5-10")]
+        age: Sweep,
+        /// Lengths of the section prefix, in bits.
+        #[structopt(long, default_value = "0-8")]
+        prefix_len: Sweep,
+    },
+    /// The share of the token supply of a section, as the network grows.
+    Supply {
+        /// Lengths of the section prefix, in bits.
+        #[structopt(long, default_value = "0-64:4")]
+        prefix_len: Sweep,
+    },
+}
+
+/// Format of the printed table.
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format {:?}", value)),
+        }
+    }
+}
+
+/// The values a parameter is swept over.
+#[derive(Clone, Debug)]
+struct Sweep(Vec<u64>);
+
+impl FromStr for Sweep {
+    type Err = String;
+
+    /// Parses comma separated "VALUE"s and "START-END[:STEP]" ranges.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid value {:?}: {}", value, e))
+        };
+        let mut values = Vec::new();
+        for part in value.split(',') {
+            let (range, step) = match part.find(':') {
+                Some(index) => (&part[..index], parse(&part[index + 1..])?),
+                None => (part, 1),
+            };
+            if step == 0 {
+                return Err(format!("Invalid step in {:?}", part));
+            }
+            match range.find('-') {
+                Some(index) => {
+                    let (start, end) = (parse(&range[..index])?, parse(&range[index + 1..])?);
+                    let mut value = start;
+                    while value <= end {
+                        values.push(value);
+                        value = match value.checked_add(step) {
+                            Some(value) => value,
+                            None => break,
+                        };
+                    }
+                }
+                None => values.push(parse(range)?),
+            }
+        }
+        Ok(Self(values))
+    }
+}
+
+impl Sweep {
+    /// The values, each of which must fit a `T`.
+    fn values<T: TryFrom<u64>>(&self, name: &str) -> Result<Vec<T>, String> {
+        self.0
+            .iter()
+            .map(|value| {
+                T::try_from(*value).map_err(|_| format!("{} {} is out of range", name, value))
+            })
+            .collect()
+    }
+
+    /// The values, each of which must be the length of a section prefix.
+    fn prefix_lens(&self) -> Result<Vec<usize>, String> {
+        match self.0.iter().find(|value| **value > MAX_PREFIX_LEN) {
+            Some(value) => Err(format!(
+                "prefix-len {} is out of range, the longest prefix is {} bits",
+                value, MAX_PREFIX_LEN
+            )),
+            None => self.values("prefix-len"),
+        }
+    }
+}
+
+/// The rows of a table, with the names of its columns.
+struct Rows {
+    columns: &'static [&'static str],
+    rows: Vec<Vec<Value>>,
+}
+
+impl Rows {
+    fn print(&self, format: Format) -> Result<(), String> {
+        match format {
+            Format::Csv => {
+                println!("{}", self.columns.join(","));
+                for row in &self.rows {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .map(|cell| match cell {
+                            Value::String(value) => value.clone(),
+                            value => value.to_string(),
+                        })
+                        .collect();
+                    println!("{}", cells.join(","));
+                }
+            }
+            Format::Json => {
+                let rows: Vec<Value> = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let object: Map<String, Value> = self
+                            .columns
+                            .iter()
+                            .map(|column| column.to_string())
+                            .zip(row.iter().cloned())
+                            .collect();
+                        Value::Object(object)
+                    })
+                    .collect();
+                let json = serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?;
+                println!("{}", json);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), String> {
+    let args = Args::from_args();
+    let rows = match args.table {
+        Table::StoreCost {
+            bytes,
+            adults,
+            full_adults,
+            prefix_len,
+            pricing,
+        } => {
+            let pricing = if pricing.is_empty() {
+                vec![Pricing::Curve, Pricing::Linear]
+            } else {
+                pricing
+            };
+            store_cost(
+                &bytes.values("bytes")?,
+                &adults.values("adults")?,
+                &full_adults.values("full-adults")?,
+                &prefix_len.prefix_lens()?,
+                &pricing,
+            )
+        }
+        Table::Reward { age, prefix_len } => {
+            reward(&age.values("age")?, &prefix_len.prefix_lens()?)
+        }
+        Table::Supply { prefix_len } => supply(&prefix_len.prefix_lens()?),
+    };
+    rows.print(args.format)
+}
+
+fn store_cost(
+    bytes: &[u64],
+    adults: &[u8],
+    full_adults: &[u8],
+    prefix_len: &[usize],
+    pricing: &[Pricing],
+) -> Rows {
+    let mut rows = Vec::new();
+    for pricing in pricing {
+        let policy = pricing.policy();
+        for prefix_len in prefix_len {
+            for all_nodes in adults {
+                for full_nodes in full_adults.iter().filter(|full| *full <= all_nodes) {
+                    for bytes in bytes {
+                        let cost = policy.store_cost(*bytes, *full_nodes, *all_nodes, *prefix_len);
+                        rows.push(vec![
+                            pricing.to_string().into(),
+                            (*prefix_len).into(),
+                            (*all_nodes).into(),
+                            (*full_nodes).into(),
+                            (*bytes).into(),
+                            cost.as_nano().into(),
+                            (cost.as_nano() as f64 / *bytes.max(&1) as f64).into(),
+                            cost.to_string().into(),
+                        ]);
+                    }
+                }
+            }
+        }
+    }
+    Rows {
+        columns: &[
+            "pricing",
+            "prefix_len",
+            "adults",
+            "full_adults",
+            "bytes",
+            "cost_nanos",
+            "nanos_per_byte",
+            "cost",
+        ],
+        rows,
+    }
+}
+
+fn reward(age: &[u8], prefix_len: &[usize]) -> Rows {
+    let mut rows = Vec::new();
+    for prefix_len in prefix_len {
+        for age in age {
+            // The reward doubles with age, so overflows a u64 of nanos for the oldest nodes.
+            if *age > MAX_REWARDED_AGE {
+                continue;
+            }
+            let reward = economics::reward(*age, *prefix_len);
+            rows.push(vec![
+                (*prefix_len).into(),
+                (*age).into(),
+                reward.as_nano().into(),
+                reward.to_string().into(),
+            ]);
+        }
+    }
+    Rows {
+        columns: &["prefix_len", "age", "reward_nanos", "reward"],
+        rows,
+    }
+}
+
+fn supply(prefix_len: &[usize]) -> Rows {
+    let rows = prefix_len
+        .iter()
+        .map(|prefix_len| {
+            let nanos = economics::max_section_nanos(*prefix_len);
+            let sections = 2_f64.powf(*prefix_len as f64);
+            vec![
+                (*prefix_len).into(),
+                sections.into(),
+                nanos.into(),
+                Token::from_nano(nanos).to_string().into(),
+            ]
+        })
+        .collect();
+    Rows {
+        columns: &["prefix_len", "sections", "section_nanos", "section_tokens"],
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sweep(value: &str) -> Result<Vec<u64>, String> {
+        value.parse::<Sweep>().map(|sweep| sweep.0)
+    }
+
+    #[test]
+    fn sweeps_parse_values_and_ranges() -> Result<(), String> {
+        assert_eq!(vec![3], sweep("3")?);
+        assert_eq!(vec![0, 4, 5, 6], sweep("0, 4-6")?);
+        assert_eq!(vec![1024, 2048, 3072, 4096], sweep("1024-4096:1024")?);
+        assert_eq!(vec![0, 4, 8], sweep("0-10:4")?);
+        assert_eq!(Vec::<u64>::new(), sweep("5-4")?);
+        assert_eq!(
+            vec![u64::MAX - 1],
+            sweep(&format!("{}-{}:2", u64::MAX - 1, u64::MAX))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_sweeps_are_rejected() {
+        for value in &["", "a", "1-", "-1", "1-4:0", "1-4:x", "1,,2", "-1-4"] {
+            assert!(sweep(value).is_err(), "{:?} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() -> Result<(), String> {
+        assert_eq!(
+            vec![0_u8, 255],
+            "0,255".parse::<Sweep>()?.values::<u8>("age")?
+        );
+        assert!("256".parse::<Sweep>()?.values::<u8>("age").is_err());
+        assert_eq!(
+            vec![0, 64, 256],
+            "0,64,256".parse::<Sweep>()?.prefix_lens()?
+        );
+        assert!("64-257".parse::<Sweep>()?.prefix_lens().is_err());
+        Ok(())
+    }
+}
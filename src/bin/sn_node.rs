@@ -29,8 +29,11 @@
 
 use log::{self, error, info};
 use self_update::{cargo_crate_version, Status};
-use sn_node::{self, utils, write_connection_info, Config, Node};
-use std::{io::Write, process};
+use sn_node::{
+    self, export_archive, import_archive, migrate_dirs, reconcile_used_space, utils,
+    write_connection_info, Command, Config, Node,
+};
+use std::{io::Write, path::Path, process};
 use structopt::{clap, StructOpt};
 
 const IGD_ERROR_MESSAGE: &str = "Automatic Port forwarding Failed. Check if UPnP is enabled in your router's settings and try again. \
@@ -60,11 +63,19 @@ async fn run_node() {
     let mut config = match Config::new() {
         Ok(cfg) => cfg,
         Err(e) => {
-            println!("Failed to create Config: {:?}", e);
+            println!("Failed to create Config: {}", e);
             process::exit(1);
         }
     };
 
+    if config.print_config() {
+        match config.describe() {
+            Ok(description) => println!("{}", description),
+            Err(e) => println!("Failed to print config: {}", e),
+        }
+        return;
+    }
+
     if let Some(c) = &config.completions() {
         match c.parse::<clap::Shell>() {
             Ok(shell) => match gen_completions_for_shell(shell) {
@@ -89,6 +100,13 @@ async fn run_node() {
 
     info!("Node PID is: {:?}", std::process::id());
 
+    match config.command() {
+        Some(Command::Fsck) => fsck(&config).await,
+        Some(Command::Export { to }) => export(&config, to).await,
+        Some(Command::Import { from }) => import(&config, from).await,
+        None => (),
+    }
+
     if config.update() || config.update_only() {
         match update() {
             Ok(status) => {
@@ -150,7 +168,10 @@ async fn run_node() {
         }
     }
 
-    match node.run().await {
+    let result = node.run().await;
+    // Flushes what the node buffers in memory to disk, as `process::exit` skips destructors.
+    drop(node);
+    match result {
         Ok(()) => process::exit(0),
         Err(e) => {
             println!("Cannot start node due to error: {:?}", e);
@@ -160,6 +181,78 @@ async fn run_node() {
     }
 }
 
+/// Reconciles the used space records of the chunk stores with their contents, and exits.
+async fn fsck(config: &Config) {
+    migrate(config).await;
+    let result = match config.chunk_dirs() {
+        Ok(chunk_dirs) => {
+            reconcile_used_space(
+                &chunk_dirs,
+                config.chunk_store_backend(),
+                config.chunk_dir_depth(),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(reports) => {
+            for report in reports {
+                println!("{}", report);
+            }
+            process::exit(0);
+        }
+        Err(e) => {
+            println!("Failed to check used space: {:?}", e);
+            error!("Failed to check used space: {:?}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Writes the data of the node to an archive, and exits.
+async fn export(config: &Config, to: &Path) {
+    migrate(config).await;
+    match export_archive(config, to) {
+        Ok(manifest) => {
+            println!("Exported {} to {}", manifest, to.display());
+            process::exit(0);
+        }
+        Err(e) => {
+            println!("Failed to export: {:?}", e);
+            error!("Failed to export: {:?}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Restores the data of a node from an archive, and exits.
+async fn import(config: &Config, from: &Path) {
+    match import_archive(config, from).await {
+        Ok((manifest, reports)) => {
+            println!("Imported {} from {}", manifest, from.display());
+            for report in reports {
+                println!("{}", report);
+            }
+            process::exit(0);
+        }
+        Err(e) => {
+            println!("Failed to import: {:?}", e);
+            error!("Failed to import: {:?}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Brings the data of the node up to the current format, or exits if it can't be.
+async fn migrate(config: &Config) {
+    if let Err(e) = migrate_dirs(config).await {
+        println!("Failed to migrate the node data: {:?}", e);
+        error!("Failed to migrate the node data: {:?}", e);
+        process::exit(1);
+    }
+}
+
 fn update() -> Result<Status, Box<dyn (::std::error::Error)>> {
     info!("Checking for updates...");
     let target = self_update::get_target();
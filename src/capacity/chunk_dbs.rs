@@ -6,9 +6,14 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Result};
+use crate::{
+    encryption::{Db, EncryptionKey},
+    utils, Result,
+};
 use pickledb::PickleDb;
-use std::{cell::RefCell, path::Path, rc::Rc};
+use serde::{Deserialize, Serialize};
+use sn_data_types::BlobAddress;
+use std::{cell::RefCell, collections::BTreeSet, path::Path, rc::Rc};
 
 const BLOB_META_DB_NAME: &str = "immutable_data.db";
 const HOLDER_META_DB_NAME: &str = "holder_data.db";
@@ -17,16 +22,26 @@ const FULL_ADULTS_DB_NAME: &str = "full_adults.db";
 
 #[derive(Clone)]
 pub struct ChunkHolderDbs {
-    pub metadata: Rc<RefCell<PickleDb>>,
-    pub holders: Rc<RefCell<PickleDb>>,
+    pub metadata: Rc<RefCell<Db>>,
+    pub holders: Rc<RefCell<Db>>,
+    // Only holds the ids of full adults, so isn't encrypted.
     pub full_adults: Rc<RefCell<PickleDb>>,
 }
 
+/// The chunks an adult has been sent to store, as kept in the holders db.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct HolderMetadata {
+    pub chunks: BTreeSet<BlobAddress>,
+}
+
 impl ChunkHolderDbs {
-    ///
-    pub fn new(path: &Path) -> Result<Self> {
+    /// Opens the dbs at `path`, encrypting the values of those holding chunk metadata with
+    /// `encryption_key`, if any.
+    pub fn new(path: &Path, encryption_key: Option<EncryptionKey>) -> Result<Self> {
         let metadata = utils::new_auto_dump_db(path, BLOB_META_DB_NAME)?;
+        let metadata = Db::new(metadata, encryption_key.clone());
         let holders = utils::new_auto_dump_db(path, HOLDER_META_DB_NAME)?;
+        let holders = Db::new(holders, encryption_key);
         let full_adults = utils::new_auto_dump_db(path, FULL_ADULTS_DB_NAME)?;
         let metadata = Rc::new(RefCell::new(metadata));
         let holders = Rc::new(RefCell::new(holders));
@@ -7,13 +7,29 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod chunk_dbs;
+mod pricing;
+mod quotes;
 mod rate_limit;
+mod report;
 
-use crate::Result;
-pub use chunk_dbs::ChunkHolderDbs;
+use crate::{config_handler::DEFAULT_MAX_CAPACITY, Result, ToDbKey};
+pub use chunk_dbs::{ChunkHolderDbs, HolderMetadata};
 use log::info;
+pub use pricing::{max_section_nanos, CurvePricing, LinearPricing, Pricing, PricingPolicy};
+pub use quotes::{PricingState, Quotes};
 pub use rate_limit::RateLimit;
+pub use report::CapacityReport;
 use sn_data_types::PublicKey;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use xor_name::XorName;
+
+/// The largest size of a chunk, in bytes.
+pub const MAX_CHUNK_SIZE: u64 = 1_000_000;
+
+/// How long an adult is counted as full after last reporting so.  Full adults keep reporting it
+/// (see `FULL_REPORT_INTERVAL`), so one which has space again, e.g. after deleting chunks or
+/// having its max capacity raised, is counted as having space once it stops.
+pub const FULL_NODE_EXPIRY: Duration = Duration::from_secs(5 * 60);
 
 /// A util for sharing the
 /// info on data capacity among the
@@ -28,20 +44,180 @@ impl Capacity {
         Self { dbs }
     }
 
-    /// Number of full chunk storing nodes in the section.
-    pub fn full_nodes(&self) -> u8 {
-        self.dbs.full_adults.borrow().total_keys() as u8
+    /// How many of the `adults` (i.e. current chunk storing nodes in the section) worth of space
+    /// is used, as the sum of their used capacity ratios, rounded.  A full adult counts as one.
+    pub fn full_nodes(&self, adults: &[XorName]) -> u8 {
+        let used: f64 = adults
+            .iter()
+            .map(|name| self.report(name).used_ratio())
+            .sum();
+        used.round() as u8
+    }
+
+    /// Whether the adult `name` has at least `bytes` of space left, as far as we know.
+    pub fn has_space(&self, name: &XorName, bytes: u64) -> bool {
+        self.report(name).free() >= bytes
+    }
+
+    /// The capacity of the adult `name`, as far as we know.  Adults only report being full, so
+    /// until then, the space it uses is that of the chunks it has been sent to store, counted
+    /// as `MAX_CHUNK_SIZE` each, out of the default max capacity of a node.
+    pub fn report(&self, name: &XorName) -> CapacityReport {
+        let max_capacity = DEFAULT_MAX_CAPACITY;
+        if self.is_full(name) {
+            return CapacityReport {
+                used: max_capacity,
+                max_capacity,
+            };
+        }
+        let chunks = name
+            .to_db_key()
+            .ok()
+            .and_then(|key| self.dbs.holders.borrow().get::<HolderMetadata>(&key))
+            .map(|holder| holder.chunks.len() as u64)
+            .unwrap_or_default();
+        CapacityReport {
+            used: chunks.saturating_mul(MAX_CHUNK_SIZE),
+            max_capacity,
+        }
+    }
+
+    fn is_full(&self, name: &XorName) -> bool {
+        let now = unix_time(SystemTime::now());
+        match self.dbs.full_adults.borrow().get::<u64>(&hex::encode(name)) {
+            Some(reported) => now.saturating_sub(reported) < FULL_NODE_EXPIRY.as_secs(),
+            None => false,
+        }
+    }
+
+    /// Records that the adult `node_id` has reported being full.
+    pub fn set_node_full(&mut self, node_id: PublicKey) -> Result<()> {
+        info!("Adult {} is full", node_id);
+        let name = XorName::from(node_id);
+        let now = unix_time(SystemTime::now());
+        self.dbs
+            .full_adults
+            .borrow_mut()
+            .set(&hex::encode(name), &now)?;
+        Ok(())
     }
 
-    ///
-    pub fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<()> {
-        info!("Increasing full node count");
-        let _ = self
+    /// Forgets about the adult `name` being full, e.g. as it has left the section.
+    pub fn remove_node(&mut self, name: XorName) -> Result<()> {
+        let _ = self.dbs.full_adults.borrow_mut().rem(&hex::encode(name))?;
+        Ok(())
+    }
+
+    /// Forgets about the full adults other than `adults`, those which are no longer full, and
+    /// any records of an older format.
+    pub fn retain_nodes(&mut self, adults: &[XorName]) -> Result<()> {
+        let now = unix_time(SystemTime::now());
+        let current: Vec<String> = adults.iter().map(hex::encode).collect();
+        let mut full_adults = self.dbs.full_adults.borrow_mut();
+        for key in full_adults.get_all() {
+            let is_full = match full_adults.get::<u64>(&key) {
+                Some(reported) => now.saturating_sub(reported) < FULL_NODE_EXPIRY.as_secs(),
+                None => false,
+            };
+            if !is_full || !current.contains(&key) {
+                let _ = full_adults.rem(&key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sn_data_types::{BlobAddress, Keypair};
+    use tempdir::TempDir;
+
+    #[test]
+    fn full_nodes_are_tracked() -> Result<()> {
+        let root = TempDir::new("capacity")?;
+        let mut capacity = Capacity::new(ChunkHolderDbs::new(root.path(), None)?);
+        let node_id = Keypair::new_ed25519(&mut rand::thread_rng()).public_key();
+        let name = XorName::from(node_id);
+        let other_name = XorName::random();
+        let adults = [name, other_name];
+
+        capacity.set_node_full(node_id)?;
+        assert_eq!(1, capacity.full_nodes(&adults));
+        // Only current adults are counted.
+        assert_eq!(0, capacity.full_nodes(&[other_name]));
+
+        capacity.remove_node(name)?;
+        assert_eq!(0, capacity.full_nodes(&adults));
+
+        // No longer counted once it stops reporting being full.
+        let reported = unix_time(SystemTime::now() - FULL_NODE_EXPIRY);
+        capacity
             .dbs
             .full_adults
             .borrow_mut()
-            .lcreate(&node_id.to_string())?
-            .ladd(&"Node Full");
+            .set(&hex::encode(name), &reported)?;
+        assert_eq!(0, capacity.full_nodes(&adults));
+        capacity.retain_nodes(&adults)?;
+        assert_eq!(0, capacity.dbs.full_adults.borrow().total_keys());
+
+        capacity.set_node_full(node_id)?;
+        capacity.retain_nodes(&[other_name])?;
+        assert_eq!(0, capacity.dbs.full_adults.borrow().total_keys());
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_held_are_priced_and_pick_holders() -> Result<()> {
+        let root = TempDir::new("capacity")?;
+        let mut capacity = Capacity::new(ChunkHolderDbs::new(root.path(), None)?);
+        let node_ids: Vec<_> = (0..3)
+            .map(|_| Keypair::new_ed25519(&mut rand::thread_rng()).public_key())
+            .collect();
+        let adults: Vec<_> = node_ids.iter().copied().map(XorName::from).collect();
+        let price = |capacity: &Capacity| {
+            let full_nodes = capacity.full_nodes(&adults);
+            CurvePricing.store_cost(MAX_CHUNK_SIZE, full_nodes, adults.len() as u8, 0)
+        };
+        let empty_price = price(&capacity);
+        assert_eq!(0, capacity.full_nodes(&adults));
+
+        // Two adults 60% full, and one which holds nothing.
+        let chunks = (DEFAULT_MAX_CAPACITY as f64 * 0.6) as u64 / MAX_CHUNK_SIZE;
+        let holder = HolderMetadata {
+            chunks: (0..chunks)
+                .map(|_| BlobAddress::Public(XorName::random()))
+                .collect(),
+        };
+        for name in &adults[..2] {
+            capacity
+                .dbs
+                .holders
+                .borrow_mut()
+                .set(&name.to_db_key()?, &holder)?;
+        }
+        assert_eq!(1, capacity.full_nodes(&adults));
+        let used_price = price(&capacity);
+        assert!(used_price > empty_price);
+
+        let free = DEFAULT_MAX_CAPACITY - chunks * MAX_CHUNK_SIZE;
+        assert!(capacity.has_space(&adults[0], free));
+        assert!(!capacity.has_space(&adults[0], free + 1));
+        assert!(capacity.has_space(&adults[2], DEFAULT_MAX_CAPACITY));
+
+        // A full adult counts as all used, whatever it holds.
+        capacity.set_node_full(node_ids[2])?;
+        assert_eq!(2, capacity.full_nodes(&adults));
+        assert!(!capacity.has_space(&adults[2], 1));
+        assert!(price(&capacity) > used_price);
+
         Ok(())
     }
 }
@@ -0,0 +1,100 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The length of the periods store costs are quoted for.  The periods are counted from the unix
+/// epoch, so all Elders are in the same one, and during each they quote the prices of the
+/// pricing state of the section as of its start.  A quote is honoured until the end of the
+/// period after the one it was made in.
+pub const QUOTE_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// The state of the section store costs are priced on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PricingState {
+    /// How many adults worth of space is used, see `Capacity::full_nodes`.
+    pub full_nodes: u8,
+    /// The number of adults.
+    pub all_nodes: u8,
+    /// The length of the section prefix.
+    pub prefix_len: usize,
+}
+
+/// The pricing states store costs were quoted with, by period.
+#[derive(Clone, Debug, Default)]
+pub struct Quotes {
+    states: BTreeMap<u64, PricingState>,
+}
+
+impl Quotes {
+    /// The pricing state to quote with at `now`: that of the first quote of its period, which is
+    /// `current` if there wasn't any yet.  Those of earlier periods than the previous are dropped.
+    pub fn quote(&mut self, current: PricingState, now: SystemTime) -> PricingState {
+        let period = quote_period(now);
+        self.states
+            .retain(|quoted, _| quoted.saturating_add(1) >= period);
+        *self.states.entry(period).or_insert(current)
+    }
+
+    /// The pricing states of the quotes honoured at `now`: those of its period and the previous.
+    pub fn honoured(&mut self, current: PricingState, now: SystemTime) -> Vec<PricingState> {
+        let quoted = self.quote(current, now);
+        let previous = quote_period(now).saturating_sub(1);
+        let mut states = vec![quoted];
+        states.extend(self.states.get(&previous));
+        states
+    }
+}
+
+fn quote_period(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / QUOTE_PERIOD.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quotes_hold_for_their_period_and_are_honoured_in_the_next() {
+        let start = UNIX_EPOCH + QUOTE_PERIOD * 1000;
+        let state = PricingState {
+            full_nodes: 1,
+            all_nodes: 10,
+            prefix_len: 0,
+        };
+        let fuller = PricingState {
+            full_nodes: 5,
+            ..state
+        };
+        // Two Elders, quoting at slightly different times, with the state changing in between.
+        let mut quotes = Quotes::default();
+        let mut other_quotes = Quotes::default();
+        assert_eq!(state, quotes.quote(state, start));
+        assert_eq!(
+            state,
+            other_quotes.quote(state, start + Duration::from_secs(2))
+        );
+        assert_eq!(state, quotes.quote(fuller, start + Duration::from_secs(3)));
+        assert_eq!(state, other_quotes.quote(fuller, start + QUOTE_PERIOD / 2));
+
+        // The next period is quoted with the new state, but the last quotes are still honoured.
+        let next = start + QUOTE_PERIOD + Duration::from_secs(1);
+        assert_eq!(fuller, quotes.quote(fuller, next));
+        assert_eq!(vec![fuller, state], other_quotes.honoured(fuller, next));
+
+        // But no longer after that.
+        let later = start + QUOTE_PERIOD * 2;
+        assert!(!quotes.honoured(fuller, later).contains(&state));
+        assert!(!other_quotes.honoured(fuller, later).contains(&state));
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use serde::{Deserialize, Serialize};
+
+/// The storage capacity of an adult, as known to the Elders of its section.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CapacityReport {
+    /// Space used by the chunks the adult stores, in bytes.
+    pub used: u64,
+    /// Space the adult can store chunks in, in bytes.
+    pub max_capacity: u64,
+}
+
+impl CapacityReport {
+    /// Space the adult can still store chunks in, in bytes.
+    pub fn free(&self) -> u64 {
+        self.max_capacity.saturating_sub(self.used)
+    }
+
+    /// The ratio of used to max capacity, at most 1.
+    pub fn used_ratio(&self) -> f64 {
+        if self.max_capacity == 0 {
+            1.0
+        } else {
+            (self.used as f64 / self.max_capacity as f64).min(1.0)
+        }
+    }
+}
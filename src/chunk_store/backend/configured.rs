@@ -0,0 +1,121 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Backend, ChunkBackend, FsBackend, KvBackend, MemoryBackend};
+use crate::{Error, Result};
+use std::{fs, path::Path};
+
+/// File recording the kind of backend a store was written by.
+const BACKEND_FILENAME: &str = "backend";
+
+/// What a `ConfiguredBackend` is opened with.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BackendOptions {
+    pub kind: ChunkBackend,
+    /// Number of subdirectory levels chunk files are sharded into, for the `FsBackend`.
+    pub dir_depth: usize,
+}
+
+/// The backend the node is configured with, picked when the store is opened.
+///
+/// The kind of backend a persisted store was written by is recorded in it, so that a store isn't
+/// silently opened as empty by another one.  Stores from before this was recorded are taken to
+/// have been written by the `FsBackend`.
+pub(crate) enum ConfiguredBackend {
+    Fs(FsBackend),
+    Kv(KvBackend),
+    Memory(MemoryBackend),
+}
+
+/// Calls `$call` on whichever backend `$self` is, bound to `$backend`.
+macro_rules! dispatch {
+    ($self:ident, $backend:ident => $call:expr) => {
+        match $self {
+            Self::Fs($backend) => $call,
+            Self::Kv($backend) => $call,
+            Self::Memory($backend) => $call,
+        }
+    };
+}
+
+impl ConfiguredBackend {
+    /// Records `configured` as the backend of the store at `dir`, or checks that it is the one
+    /// already recorded.
+    fn check_kind(dir: &Path, configured: ChunkBackend) -> Result<()> {
+        let record = dir.join(BACKEND_FILENAME);
+        let found = if record.is_file() {
+            fs::read_to_string(&record)?.trim().parse()?
+        } else if dir.is_dir() && fs::read_dir(dir)?.next().is_some() {
+            ChunkBackend::Fs
+        } else {
+            fs::create_dir_all(dir)?;
+            configured
+        };
+        if found != configured {
+            return Err(Error::ChunkBackendMismatch {
+                dir: dir.to_path_buf(),
+                found,
+                configured,
+            });
+        }
+        if !record.is_file() {
+            fs::write(record, configured.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+impl Backend for ConfiguredBackend {
+    type Options = BackendOptions;
+
+    fn open(dir: &Path, options: BackendOptions) -> Result<Self> {
+        Ok(match options.kind {
+            ChunkBackend::Fs => {
+                Self::check_kind(dir, ChunkBackend::Fs)?;
+                Self::Fs(FsBackend::open(dir, options.dir_depth)?)
+            }
+            ChunkBackend::Kv => {
+                Self::check_kind(dir, ChunkBackend::Kv)?;
+                Self::Kv(KvBackend::open(dir, ())?)
+            }
+            ChunkBackend::Memory => Self::Memory(MemoryBackend::open(dir, ())?),
+        })
+    }
+
+    fn record_dir(&self) -> Option<&Path> {
+        dispatch!(self, backend => backend.record_dir())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        dispatch!(self, backend => backend.put(key, value))
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        dispatch!(self, backend => backend.get(key))
+    }
+
+    fn size(&self, key: &[u8]) -> Option<u64> {
+        dispatch!(self, backend => backend.size(key))
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        dispatch!(self, backend => backend.delete(key))
+    }
+
+    fn quarantine(&self, key: &[u8]) -> Result<()> {
+        dispatch!(self, backend => backend.quarantine(key))
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        dispatch!(self, backend => backend.keys())
+    }
+
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>> {
+        dispatch!(self, backend => backend.keys_after(after, limit))
+    }
+}
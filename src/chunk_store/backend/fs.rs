@@ -0,0 +1,444 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Backend;
+use crate::{utils, Error, Result};
+use log::{info, trace};
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// The max name length for a chunk file.
+const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+
+/// Directory within a store where chunks are written before being moved into place.
+const TEMP_DIR_NAME: &str = "tmp";
+
+/// Extension of the files in `TEMP_DIR_NAME`.
+const TEMP_FILE_EXTENSION: &str = "tmp";
+
+/// Directory within a store where chunk files found to be corrupt are moved to.
+const QUARANTINE_DIR_NAME: &str = "quarantine";
+
+/// File recording the number of subdirectory levels the chunks of a store are sharded into.
+const LAYOUT_FILENAME: &str = "layout";
+
+/// The max number of subdirectory levels a store can be sharded into.
+const MAX_CHUNK_DIR_DEPTH: usize = 4;
+
+/// Keeps each chunk in a file of its own, named after the hex encoded key.
+///
+/// Files are spread over `dir_depth` levels of subdirectories, named by the hex prefix of a hash of
+/// the key.  A store previously written with a different depth (including the legacy flat layout)
+/// is migrated to the requested one when opened.
+pub(crate) struct FsBackend {
+    dir: PathBuf,
+    // Number of hex-prefix subdirectory levels the chunk files are spread over.
+    dir_depth: usize,
+    // The last dir of chunk files listed by `keys_after`, so that paging through it doesn't list
+    // and sort it again for every page.
+    listing: Mutex<Option<Listing>>,
+}
+
+/// The sorted names of the chunk files in `dir`, as of its last modification.
+struct Listing {
+    dir: PathBuf,
+    modified: SystemTime,
+    names: Vec<String>,
+}
+
+impl Backend for FsBackend {
+    /// The number of subdirectory levels to spread chunk files over.
+    type Options = usize;
+
+    fn open(dir: &Path, dir_depth: usize) -> Result<Self> {
+        if dir_depth > MAX_CHUNK_DIR_DEPTH {
+            return Err(Error::InvalidChunkDirDepth(dir_depth));
+        }
+
+        if dir.is_dir() {
+            trace!("Loading ChunkStore at {}", dir.display());
+        } else {
+            Self::create_new_root(dir)?
+        }
+        Self::migrate_layout(dir, dir_depth)?;
+        Self::remove_orphaned_temp_files(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            dir_depth,
+            listing: Mutex::new(None),
+        })
+    }
+
+    fn record_dir(&self) -> Option<&Path> {
+        Some(&self.dir)
+    }
+
+    /// The value is first written and synced to a temp file, which is then renamed into place, so
+    /// that a crash never leaves a partially written chunk behind.
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let temp_path = self.temp_path(key);
+        let res = Self::write_atomically(value, &temp_path, &self.file_path(key));
+        if res.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        res.map_err(From::from)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let mut file = File::open(self.file_path(key)).map_err(|_| Error::NoSuchChunk)?;
+        let mut contents = vec![];
+        let _ = file.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    fn size(&self, key: &[u8]) -> Option<u64> {
+        fs::metadata(self.file_path(key))
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        match fs::remove_file(self.file_path(key)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves the file into `QUARANTINE_DIR_NAME`.
+    fn quarantine(&self, key: &[u8]) -> Result<()> {
+        let quarantine_path = self.dir.join(QUARANTINE_DIR_NAME).join(hex::encode(key));
+        Self::create_parent_dir(&quarantine_path)?;
+        fs::rename(self.file_path(key), &quarantine_path)?;
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        chunk_files(&self.dir, self.dir_depth)
+            .iter()
+            .filter_map(|path| chunk_key(path))
+            .collect()
+    }
+
+    /// Walks the subdirectories in order of name, only reading those at or after the one holding
+    /// `after`.  Keys are thus ordered by the hash prefix they are sharded by, then by themselves.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>> {
+        let after_path = after.map(|key| shard_names(self.dir_depth, key));
+        let mut keys = Vec::new();
+        self.walk_chunk_keys(
+            &self.dir,
+            self.dir_depth,
+            after_path.as_deref(),
+            limit,
+            &mut keys,
+        );
+        keys
+    }
+}
+
+impl FsBackend {
+    fn create_new_root(root: &Path) -> Result<()> {
+        trace!("Creating ChunkStore at {}", root.display());
+        fs::create_dir_all(root)?;
+
+        // Verify that chunk files can be created.
+        let temp_file_path = root.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
+        let _ = File::create(&temp_file_path)?;
+        fs::remove_file(temp_file_path)?;
+
+        Ok(())
+    }
+
+    /// Moves all chunk files into the layout for `dir_depth`, if they were written with another one.
+    fn migrate_layout(dir: &Path, dir_depth: usize) -> Result<()> {
+        let layout_path = dir.join(LAYOUT_FILENAME);
+        // A store without a layout record predates sharding, and so is flat.
+        let current_depth = match fs::read(&layout_path) {
+            Ok(contents) => utils::deserialise::<u64>(&contents)? as usize,
+            Err(_) => 0,
+        };
+
+        if current_depth != dir_depth {
+            info!(
+                "Migrating ChunkStore at {} from depth {} to depth {}",
+                dir.display(),
+                current_depth,
+                dir_depth
+            );
+            for old_path in chunk_files(dir, current_depth) {
+                let key = match chunk_key(&old_path) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                let new_path = shard_path(dir, dir_depth, &key);
+                if let Some(parent) = new_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&old_path, &new_path)?;
+            }
+            remove_empty_subdirs(dir, current_depth);
+        }
+
+        fs::write(layout_path, utils::serialise(&(dir_depth as u64))?)?;
+        Ok(())
+    }
+
+    /// Removes the temp files of writes that never completed (e.g. due to a crash).  The space
+    /// reserved for them is released when the used space of the store is reconciled.
+    fn remove_orphaned_temp_files(dir: &Path) -> Result<()> {
+        let entries = match fs::read_dir(dir.join(TEMP_DIR_NAME)) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(()),
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            info!("Removing orphaned temp file {}", path.display());
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the keys of the chunk files `dir_depth` levels below `dir` to `keys`, in order of
+    /// path, until it holds `limit` of them.  Only paths coming after `after_path` (as given by
+    /// `shard_names`) are included.
+    fn walk_chunk_keys(
+        &self,
+        dir: &Path,
+        dir_depth: usize,
+        after_path: Option<&[String]>,
+        limit: usize,
+        keys: &mut Vec<Vec<u8>>,
+    ) {
+        if dir_depth == 0 {
+            let after_name = after_path.and_then(|after_path| after_path.first());
+            return self.list_chunk_keys(dir, after_name.map(String::as_str), limit, keys);
+        }
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_shard_dir(path))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if keys.len() >= limit {
+                return;
+            }
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            // Only the subdirectories along the path of `after` need to be checked against it,
+            // everything after them comes after it.
+            let after_rest = match after_path {
+                Some(after_path) if !after_path.is_empty() => {
+                    match name.cmp(after_path[0].as_str()) {
+                        Ordering::Less => continue,
+                        Ordering::Equal => Some(&after_path[1..]),
+                        Ordering::Greater => None,
+                    }
+                }
+                _ => None,
+            };
+            self.walk_chunk_keys(&path, dir_depth - 1, after_rest, limit, keys);
+        }
+    }
+
+    /// Adds the keys of the chunk files in `dir` named after `after_name` to `keys`, in order of
+    /// name, until it holds `limit` of them.  The sorted listing of `dir` is kept until it is
+    /// modified, so that paging through it only lists it once.
+    fn list_chunk_keys(
+        &self,
+        dir: &Path,
+        after_name: Option<&str>,
+        limit: usize,
+        keys: &mut Vec<Vec<u8>>,
+    ) {
+        let modified = match fs::metadata(dir).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return,
+        };
+        let mut listing = match self.listing.lock() {
+            Ok(listing) => listing,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let is_current = matches!(
+            &*listing,
+            Some(listing) if listing.dir == dir && listing.modified == modified
+        );
+        if !is_current {
+            *listing = Some(Listing {
+                dir: dir.to_path_buf(),
+                modified,
+                names: chunk_file_names(dir),
+            });
+        }
+        let names = match &*listing {
+            Some(listing) => &listing.names,
+            None => return,
+        };
+        let start = match after_name {
+            Some(after_name) => {
+                match names.binary_search_by(|name| name.as_str().cmp(after_name)) {
+                    Ok(index) => index + 1,
+                    Err(index) => index,
+                }
+            }
+            None => 0,
+        };
+        let count = limit.saturating_sub(keys.len());
+        keys.extend(
+            names[start..]
+                .iter()
+                .take(count)
+                .filter_map(|name| hex::decode(name).ok()),
+        );
+    }
+
+    pub(crate) fn file_path(&self, key: &[u8]) -> PathBuf {
+        shard_path(&self.dir, self.dir_depth, key)
+    }
+
+    /// Path of the temp file a chunk is written to before being moved into place.
+    pub(crate) fn temp_path(&self, key: &[u8]) -> PathBuf {
+        self.dir
+            .join(TEMP_DIR_NAME)
+            .join(format!("{}.{}", hex::encode(key), TEMP_FILE_EXTENSION))
+    }
+
+    fn write_atomically(contents: &[u8], temp_path: &Path, file_path: &Path) -> io::Result<()> {
+        Self::create_parent_dir(temp_path)?;
+        Self::create_parent_dir(file_path)?;
+
+        let mut file = File::create(temp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+
+        fs::rename(temp_path, file_path)?;
+        // Persist the rename itself.
+        match file_path.parent() {
+            Some(parent) => sync_dir(parent),
+            None => Ok(()),
+        }
+    }
+
+    fn create_parent_dir(file_path: &Path) -> io::Result<()> {
+        match file_path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Path of the file holding the chunk with the serialised id `key`, `dir_depth` levels below `dir`.
+fn shard_path(dir: &Path, dir_depth: usize, key: &[u8]) -> PathBuf {
+    let mut path = dir.to_path_buf();
+    path.extend(shard_names(dir_depth, key));
+    path
+}
+
+/// Names of the `dir_depth` subdirectories, then of the file, making up the path of the chunk with
+/// the serialised id `key`.
+fn shard_names(dir_depth: usize, key: &[u8]) -> Vec<String> {
+    let hash = tiny_keccak::sha3_256(key);
+    hash.iter()
+        .take(dir_depth)
+        .map(|byte| format!("{:02x}", byte))
+        .chain(std::iter::once(hex::encode(key)))
+        .collect()
+}
+
+/// The sorted names of the chunk files in `dir`.
+fn chunk_file_names(dir: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && chunk_key(path).is_some())
+        .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Lists all files exactly `dir_depth` levels below `dir`.
+pub(crate) fn chunk_files(dir: &Path, dir_depth: usize) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .flat_map(|path| {
+            if dir_depth == 0 {
+                if path.is_file() {
+                    vec![path]
+                } else {
+                    vec![]
+                }
+            } else if is_shard_dir(&path) {
+                chunk_files(&path, dir_depth - 1)
+            } else {
+                vec![]
+            }
+        })
+        .collect()
+}
+
+/// Whether `path` is one of the hex-prefix subdirectories chunks are sharded into, as opposed to
+/// e.g. `TEMP_DIR_NAME` or `QUARANTINE_DIR_NAME`.
+fn is_shard_dir(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    path.is_dir() && name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+// Directories can't be opened for syncing on other platforms.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Removes the (now empty) subdirectories of a previous layout.
+fn remove_empty_subdirs(dir: &Path, dir_depth: usize) {
+    if dir_depth == 0 {
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if is_shard_dir(&path) {
+                remove_empty_subdirs(&path, dir_depth - 1);
+                // Fails, as it should, if the dir still holds chunks of the new layout.
+                let _ = fs::remove_dir(&path);
+            }
+        }
+    }
+}
+
+/// The serialised chunk id a chunk file is named after.
+fn chunk_key(path: &Path) -> Option<Vec<u8>> {
+    let file_name = path.file_name()?.to_str()?;
+    hex::decode(file_name).ok()
+}
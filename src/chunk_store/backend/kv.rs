@@ -0,0 +1,89 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Backend;
+use crate::{Error, Result};
+use sled::Db;
+use std::{
+    fs,
+    ops::Bound,
+    path::{Path, PathBuf},
+};
+
+/// Name of the db directory within the store dir.
+const DB_DIR_NAME: &str = "chunks.db";
+
+/// Keeps chunks in an embedded, ordered key-value db (sled), which only writes the changed values
+/// to disk.
+pub(crate) struct KvBackend {
+    dir: PathBuf,
+    db: Db,
+}
+
+impl Backend for KvBackend {
+    type Options = ();
+
+    fn open(dir: &Path, _options: ()) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            db: sled::open(dir.join(DB_DIR_NAME))?,
+        })
+    }
+
+    fn record_dir(&self) -> Option<&Path> {
+        Some(&self.dir)
+    }
+
+    /// The value is synced to disk before returning, as chunk files are.
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let _ = self.db.insert(key, value)?;
+        let _ = self.db.flush()?;
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        self.db
+            .get(key)?
+            .map(|value| value.to_vec())
+            .ok_or(Error::NoSuchChunk)
+    }
+
+    fn size(&self, key: &[u8]) -> Option<u64> {
+        self.db.get(key).ok()?.map(|value| value.len() as u64)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        if self.db.remove(key)?.is_some() {
+            let _ = self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        self.db
+            .iter()
+            .keys()
+            .filter_map(|key| key.ok().map(|key| key.to_vec()))
+            .collect()
+    }
+
+    /// Walks the keys in the order of the db, without listing the others.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after),
+            None => Bound::Unbounded,
+        };
+        self.db
+            .range::<&[u8], _>((start, Bound::Unbounded))
+            .keys()
+            .filter_map(|key| key.ok().map(|key| key.to_vec()))
+            .take(limit)
+            .collect()
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Backend;
+use crate::{Error, Result};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+/// Keeps chunks in memory only, e.g. for tests and simulations running many nodes in one process.
+/// Nothing survives a restart.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryBackend {
+    values: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl Backend for MemoryBackend {
+    type Options = ();
+
+    fn open(_dir: &Path, _options: ()) -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    fn record_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let _ = self.write()?.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>> {
+        self.read()?.get(key).cloned().ok_or(Error::NoSuchChunk)
+    }
+
+    fn size(&self, key: &[u8]) -> Option<u64> {
+        self.read().ok()?.get(key).map(|value| value.len() as u64)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        let _ = self.write()?.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<Vec<u8>> {
+        self.read()
+            .map(|values| values.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl MemoryBackend {
+    fn read(&self) -> Result<RwLockReadGuard<'_, HashMap<Vec<u8>, Vec<u8>>>> {
+        self.values
+            .read()
+            .map_err(|_| Error::Logic("Poisoned chunk store lock".to_string()))
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<'_, HashMap<Vec<u8>, Vec<u8>>>> {
+        self.values
+            .write()
+            .map_err(|_| Error::Logic("Poisoned chunk store lock".to_string()))
+    }
+}
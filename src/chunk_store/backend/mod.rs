@@ -0,0 +1,125 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Storage backends holding the serialised chunks of a `ChunkStore`.
+
+mod configured;
+mod fs;
+mod kv;
+mod memory;
+
+#[cfg(test)]
+pub(crate) use self::fs::chunk_files;
+pub(crate) use self::{
+    configured::{BackendOptions, ConfiguredBackend},
+    fs::FsBackend,
+    kv::KvBackend,
+    memory::MemoryBackend,
+};
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    path::Path,
+    str::FromStr,
+};
+
+/// The storage backends a node can be configured to keep its chunks in.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChunkBackend {
+    /// A file per chunk (`FsBackend`).
+    #[default]
+    Fs,
+    /// An embedded key-value db (`KvBackend`).
+    Kv,
+    /// Memory only, losing all chunks on restart (`MemoryBackend`).
+    Memory,
+}
+
+impl FromStr for ChunkBackend {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "fs" => Ok(Self::Fs),
+            "kv" => Ok(Self::Kv),
+            "memory" => Ok(Self::Memory),
+            _ => Err(Error::Logic(format!(
+                "Config file error: unknown chunk store backend {:?}",
+                value
+            ))),
+        }
+    }
+}
+
+impl Display for ChunkBackend {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Fs => write!(formatter, "fs"),
+            Self::Kv => write!(formatter, "kv"),
+            Self::Memory => write!(formatter, "memory"),
+        }
+    }
+}
+
+/// Raw key-value storage of the chunks of a single `ChunkStore`.
+///
+/// Keys are serialised chunk ids, and values serialised chunks.  Keeping track of the used space,
+/// and (de)serialising, is left to the `ChunkStore`.
+///
+/// All methods may block, and are only ever called by the `ChunkStore` on the blocking thread pool.
+pub(crate) trait Backend: Sized + Send + Sync + 'static {
+    /// What the backend is opened with, besides the store dir.
+    type Options: Clone + Send + 'static;
+
+    /// Opens the backend for the store at `dir`, creating it if it doesn't exist.
+    fn open(dir: &Path, options: Self::Options) -> Result<Self>;
+
+    /// The directory to keep the used space record of the store in, or `None` if the backend
+    /// doesn't persist anything.
+    fn record_dir(&self) -> Option<&Path>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Returns the value stored under `key`, or `Error::NoSuchChunk` if there is none.
+    fn get(&self, key: &[u8]) -> Result<Vec<u8>>;
+
+    /// The space taken by the value stored under `key`, if any.
+    fn size(&self, key: &[u8]) -> Option<u64>;
+
+    /// Deletes the value stored under `key`.  Does nothing if there is none.
+    fn delete(&self, key: &[u8]) -> Result<()>;
+
+    /// Removes the value stored under `key` from the store, as it is corrupt.  Backends able to
+    /// keep it aside for inspection should do so, the others just delete it.
+    fn quarantine(&self, key: &[u8]) -> Result<()> {
+        self.delete(key)
+    }
+
+    /// Lists all keys with a value stored.
+    fn keys(&self) -> Vec<Vec<u8>>;
+
+    /// Lists up to `limit` keys with a value stored, coming after `after` (or from the start if
+    /// `None`) in an order of the backend's choosing, which is the same on every call.  `after`
+    /// needn't still have a value stored.
+    ///
+    /// Backends able to walk their keys in order without listing them all should do so.
+    fn keys_after(&self, after: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>> {
+        let mut keys = self.keys();
+        keys.sort();
+        keys.into_iter()
+            .filter(|key| match after {
+                Some(after) => key.as_slice() > after,
+                None => true,
+            })
+            .take(limit)
+            .collect()
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display, Formatter},
+};
+
+/// Hit and miss counters of a `ChunkCache`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    /// Number of reads served from the cache.
+    pub hits: u64,
+    /// Number of reads that had to go to the store.
+    pub misses: u64,
+    /// Space taken by the cached chunks, in serialised bytes.
+    pub size: u64,
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} hits, {} misses, {} bytes cached",
+            self.hits, self.misses, self.size
+        )
+    }
+}
+
+/// A cache of recently read chunks, bounded by their total serialised size.  Once full, the least
+/// recently read chunks are evicted first.
+pub(super) struct ChunkCache<T> {
+    capacity: u64,
+    entries: HashMap<Vec<u8>, Entry<T>>,
+    // Keys of the entries by the tick they were last read at, least recent first.
+    recency: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+struct Entry<T> {
+    chunk: T,
+    size: u64,
+    last_read: u64,
+}
+
+impl<T: Clone> ChunkCache<T> {
+    /// Creates an empty cache holding up to `capacity` bytes of chunks.
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the chunk cached under `key`, if any, counting the hit or miss.
+    pub fn get(&mut self, key: &[u8]) -> Option<T> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                let _ = self.recency.remove(&entry.last_read);
+                let _ = self.recency.insert(tick, key.to_vec());
+                entry.last_read = tick;
+                self.stats.hits += 1;
+                Some(entry.chunk.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches `chunk` under `key`, evicting the least recently read chunks to make room.  Chunks
+    /// larger than the whole cache aren't cached.
+    pub fn insert(&mut self, key: Vec<u8>, chunk: T, size: u64) {
+        self.remove(&key);
+        if size > self.capacity {
+            return;
+        }
+        while self.stats.size + size > self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(evicted) = self.recency.remove(&oldest) {
+                self.remove(&evicted);
+            }
+        }
+        self.tick += 1;
+        let _ = self.recency.insert(self.tick, key.clone());
+        let _ = self.entries.insert(
+            key,
+            Entry {
+                chunk,
+                size,
+                last_read: self.tick,
+            },
+        );
+        self.stats.size += size;
+    }
+
+    /// Drops the chunk cached under `key`, if any.
+    pub fn remove(&mut self, key: &[u8]) {
+        if let Some(entry) = self.entries.remove(key) {
+            let _ = self.recency.remove(&entry.last_read);
+            self.stats.size -= entry.size;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkCache;
+
+    #[test]
+    fn least_recently_read_chunks_are_evicted() {
+        let mut cache = ChunkCache::new(10);
+        cache.insert(vec![1], 1, 4);
+        cache.insert(vec![2], 2, 4);
+        assert_eq!(Some(1), cache.get(&[1]));
+
+        // Evicts 2, as 1 was read since it was inserted.
+        cache.insert(vec![3], 3, 4);
+        assert_eq!(None, cache.get(&[2]));
+        assert_eq!(Some(1), cache.get(&[1]));
+        assert_eq!(Some(3), cache.get(&[3]));
+
+        // Too large to be cached at all.
+        cache.insert(vec![4], 4, 11);
+        assert_eq!(None, cache.get(&[4]));
+        assert_eq!(Some(1), cache.get(&[1]));
+
+        cache.remove(&[1]);
+        assert_eq!(None, cache.get(&[1]));
+
+        let stats = cache.stats();
+        assert_eq!(4, stats.hits);
+        assert_eq!(3, stats.misses);
+        assert_eq!(4, stats.size);
+    }
+}
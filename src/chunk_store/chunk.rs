@@ -10,11 +10,18 @@ use crate::ToDbKey;
 use serde::{de::DeserializeOwned, Serialize};
 use xor_name::XorName;
 
-pub(crate) trait Chunk: Serialize + DeserializeOwned {
+pub(crate) trait Chunk: Clone + Serialize + DeserializeOwned {
     type Id: ChunkId;
     fn id(&self) -> &Self::Id;
 }
 
-pub(crate) trait ChunkId: ToDbKey + PartialEq + Eq + DeserializeOwned {}
+pub(crate) trait ChunkId: ToDbKey + PartialEq + Eq + DeserializeOwned {
+    /// The name of the chunk on the network, which decides which section holds it.
+    fn name(&self) -> XorName;
+}
 
-impl ChunkId for XorName {}
+impl ChunkId for XorName {
+    fn name(&self) -> XorName {
+        *self
+    }
+}
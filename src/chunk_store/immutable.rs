@@ -8,6 +8,7 @@
 
 use super::chunk::{Chunk, ChunkId};
 use sn_data_types::{Blob, BlobAddress};
+use xor_name::XorName;
 
 impl Chunk for Blob {
     type Id = BlobAddress;
@@ -19,4 +20,8 @@ impl Chunk for Blob {
     }
 }
 
-impl ChunkId for BlobAddress {}
+impl ChunkId for BlobAddress {
+    fn name(&self) -> XorName {
+        *self.name()
+    }
+}
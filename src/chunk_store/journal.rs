@@ -0,0 +1,204 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! An append-only, on-disk journal of the used space of a single `ChunkStore`.
+//!
+//! The file starts with a checkpoint of the used space, followed by the increases and decreases
+//! made since.  Changes are buffered in memory and only appended (and synced) on an explicit
+//! flush, which the node runs every `FLUSH_INTERVAL` and on shutdown, when a change is recorded
+//! over `FLUSH_INTERVAL` after the last flush, or when the journal is dropped.  Once enough changes
+//! have been appended, the file is compacted back into a single checkpoint.
+
+use crate::Result;
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+/// Max time changes are buffered before being written to disk.
+pub(crate) const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of changes appended to the journal after which it is compacted.
+const MAX_ENTRIES: u64 = 4096;
+
+/// Serialised size of the checkpoint.
+const CHECKPOINT_SIZE: usize = 8;
+
+/// A change to the used space.
+#[derive(Debug, Deserialize, Serialize)]
+enum Entry {
+    Increase(u64),
+    Decrease(u64),
+}
+
+#[derive(Debug)]
+pub(super) struct Journal {
+    path: PathBuf,
+    file: File,
+    // The used space, including the changes not yet flushed.
+    value: u64,
+    // Serialised entries not yet written to `file`.
+    pending: Vec<u8>,
+    // Number of entries in `file` after its checkpoint.
+    entries: u64,
+    last_flush: Instant,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if it doesn't exist, and replays it.
+    ///
+    /// Returns `None` as the replayed value if the checkpoint can't be read.  The caller is then
+    /// expected to rebuild the journal via `reset`.
+    pub async fn open(path: &Path) -> Result<(Self, Option<u64>)> {
+        let mut contents = vec![];
+        let replayed = match File::open(path).await {
+            Ok(mut file) => {
+                let _ = file.read_to_end(&mut contents).await?;
+                replay(&contents)
+            }
+            Err(_) => {
+                fs::write(path, bincode::serialize(&0_u64)?).await?;
+                Some((0, 0))
+            }
+        };
+        let file = OpenOptions::new().append(true).open(path).await?;
+        let (value, entries) = replayed.unwrap_or((0, 0));
+        let mut journal = Self {
+            path: path.to_path_buf(),
+            file,
+            value,
+            pending: vec![],
+            entries,
+            last_flush: Instant::now(),
+        };
+        // Drop any torn entry, so that new entries aren't appended after it.
+        let replayed_len = CHECKPOINT_SIZE + entries as usize * entry_size();
+        if replayed.is_some() && contents.len() > replayed_len {
+            journal.compact().await?;
+        }
+        Ok((journal, replayed.map(|(value, _)| value)))
+    }
+
+    /// The used space, including the changes not yet flushed.
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Records an increase, flushing if due.
+    pub async fn increase(&mut self, consumed: u64) -> Result<()> {
+        self.value = self.value.saturating_add(consumed);
+        self.record(Entry::Increase(consumed)).await
+    }
+
+    /// Records a decrease, flushing if due.
+    pub async fn decrease(&mut self, released: u64) -> Result<()> {
+        self.value = self.value.saturating_sub(released);
+        self.record(Entry::Decrease(released)).await
+    }
+
+    /// Replaces the journal with a checkpoint of `value`.
+    pub async fn reset(&mut self, value: u64) -> Result<()> {
+        self.value = value;
+        self.compact().await
+    }
+
+    /// Appends the buffered changes to the file and syncs it.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.last_flush = Instant::now();
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.file.write_all(&self.pending).await?;
+        self.file.sync_data().await?;
+        self.entries += (self.pending.len() / entry_size()) as u64;
+        self.pending.clear();
+        if self.entries >= MAX_ENTRIES {
+            self.compact().await?;
+        }
+        Ok(())
+    }
+
+    async fn record(&mut self, entry: Entry) -> Result<()> {
+        bincode::serialize_into(&mut self.pending, &entry)?;
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Atomically rewrites the file as a checkpoint of the current value.
+    async fn compact(&mut self) -> Result<()> {
+        trace!("Compacting used space journal at {}", self.path.display());
+        let temp_path = self.path.with_extension("tmp");
+        let mut temp_file = File::create(&temp_path).await?;
+        temp_file
+            .write_all(&bincode::serialize(&self.value)?)
+            .await?;
+        temp_file.sync_all().await?;
+        fs::rename(&temp_path, &self.path).await?;
+        self.file = OpenOptions::new().append(true).open(&self.path).await?;
+        self.pending.clear();
+        self.entries = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let result = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| {
+                file.write_all(&self.pending)?;
+                file.sync_data()
+            });
+        if let Err(error) = result {
+            warn!(
+                "Could not flush used space journal at {}: {}",
+                self.path.display(),
+                error
+            );
+        }
+    }
+}
+
+/// Serialised size of an entry.
+fn entry_size() -> usize {
+    bincode::serialized_size(&Entry::Increase(0)).unwrap_or_default() as usize
+}
+
+/// The value recorded by a journal's contents, and the number of entries after its checkpoint.
+/// A torn entry at the end, from an interrupted flush, is ignored.
+fn replay(contents: &[u8]) -> Option<(u64, u64)> {
+    if contents.len() < CHECKPOINT_SIZE {
+        return None;
+    }
+    let (checkpoint, entries) = contents.split_at(CHECKPOINT_SIZE);
+    let mut value = bincode::deserialize::<u64>(checkpoint).ok()?;
+    let mut count = 0;
+    for entry in entries.chunks_exact(entry_size()) {
+        match bincode::deserialize(entry) {
+            Ok(Entry::Increase(consumed)) => value = value.saturating_add(consumed),
+            Ok(Entry::Decrease(released)) => value = value.saturating_sub(released),
+            Err(_) => break,
+        }
+        count += 1;
+    }
+    Some((value, count))
+}
@@ -8,130 +8,311 @@
 
 //! A simple, persistent, disk-based key-value store.
 
+mod backend;
+mod cache;
 mod chunk;
 mod immutable;
+mod journal;
 mod mutable;
 mod sequence;
+mod stats;
 #[cfg(test)]
 mod tests;
 mod used_space;
 
+use crate::encryption::EncryptionKey;
 use crate::error::{Error, Result};
 use crate::utils;
+pub use backend::ChunkBackend;
+use backend::{Backend, FsBackend};
+pub(crate) use backend::{BackendOptions, ConfiguredBackend};
+#[cfg(test)]
+pub(crate) use backend::{KvBackend, MemoryBackend};
+pub(crate) use cache::CacheStats;
+use cache::ChunkCache;
 use chunk::{Chunk, ChunkId};
-use log::{info, trace};
+pub(crate) use journal::FLUSH_INTERVAL as USED_SPACE_FLUSH_INTERVAL;
+use log::{error, info, trace, warn};
+use serde::{Deserialize, Serialize};
 use sn_data_types::{Blob, Map, Sequence};
+use stats::OpCounters;
+pub use stats::{ChunkStoreStats, SizeBucket, StorageStats};
 use std::{
-    fs::{self, DirEntry, File, Metadata},
-    io::{Read, Write},
+    collections::HashSet,
+    fs, io,
     marker::PhantomData,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task,
 };
 use used_space::StoreId;
-pub use used_space::UsedSpace;
+pub(crate) use used_space::USED_SPACE_FILENAME;
+pub use used_space::{
+    KindSpaceStats, UsedSpace, UsedSpaceReport, UsedSpaceStats, VolumeSpaceStats,
+};
+use xor_name::Prefix;
 
 const CHUNK_STORE_DIR: &str = "chunks";
 
-/// The max name length for a chunk file.
-const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+/// File recording that all chunks of a store have been encrypted.
+const ENCRYPTED_FILENAME: &str = "encrypted";
 
-pub(crate) type BlobChunkStore = ChunkStore<Blob>;
-pub(crate) type MapChunkStore = ChunkStore<Map>;
-pub(crate) type SequenceChunkStore = ChunkStore<Sequence>;
+/// The number of keys `ChunkStore::stats` reads the sizes of at a time.
+const STATS_BATCH_SIZE: usize = 1000;
 
-/// `ChunkStore` is a store of data held as serialised files on disk, implementing a maximum disk
-/// usage to restrict storage.
-pub(crate) struct ChunkStore<T: Chunk> {
-    dir: PathBuf,
+/// The max number of backend operations a `ChunkStore` runs on the blocking thread pool at once.
+const MAX_CONCURRENT_IO: usize = 8;
+
+pub(crate) type BlobChunkStore = ChunkStore<Blob, ConfiguredBackend>;
+pub(crate) type MapChunkStore = ChunkStore<Map, ConfiguredBackend>;
+pub(crate) type SequenceChunkStore = ChunkStore<Sequence, ConfiguredBackend>;
+
+/// A position in the keys of a `ChunkStore`, to resume listing them from with
+/// `ChunkStore::keys_page`.  It can be serialised to persist it, e.g. across restarts.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct KeysCursor {
+    /// Index of the volume being listed.
+    volume: usize,
+    /// The last key listed from that volume, if any.
+    last_key: Option<Vec<u8>>,
+}
+
+/// A page of keys of a `ChunkStore`.
+#[derive(Debug)]
+pub(crate) struct KeysPage<Id> {
+    pub keys: Vec<Id>,
+    /// Where the next page starts, or `None` if this is the last one.
+    pub next: Option<KeysCursor>,
+}
+
+/// `ChunkStore` is a store of serialised data chunks, held by a storage `Backend` (by default as
+/// files on disk), implementing a maximum disk usage to restrict storage.
+///
+/// A store can be spread over several root directories (e.g. on separate disks), each holding its
+/// own subset of the chunks in a `Volume`.
+///
+/// All backend operations, which may block on disk I/O, are run on the blocking thread pool, so
+/// that a slow disk doesn't stall the rest of the node.
+pub(crate) struct ChunkStore<T: Chunk, B: Backend = FsBackend> {
+    volumes: Vec<Volume<B>>,
+    // Recently read chunks, if caching is enabled.
+    cache: Option<Mutex<ChunkCache<T>>>,
+    // Key the chunks are encrypted with at rest, if encryption is enabled.
+    encryption: Option<EncryptionKey>,
     // Maximum space allowed for all `ChunkStore`s to consume.
     used_space: UsedSpace,
-    id: StoreId,
+    counters: Mutex<OpCounters>,
     _phantom: PhantomData<T>,
 }
 
-impl<T> ChunkStore<T>
+/// The part of a `ChunkStore` within one of its root directories.
+struct Volume<B> {
+    dir: PathBuf,
+    backend: Arc<B>,
+    // Bounds the number of backend operations in flight at once.
+    io_permits: Arc<Semaphore>,
+    id: StoreId,
+    // Keys of the chunks stored, so that looking them up doesn't go to the backend.  Rebuilt
+    // whenever the used space is reconciled.
+    index: Mutex<HashSet<Vec<u8>>>,
+}
+
+impl<T, B> ChunkStore<T, B>
 where
     T: Chunk,
+    B: Backend,
     Self: Subdir,
 {
-    /// Creates a new `ChunkStore` at location `root/CHUNK_STORE_DIR/<chunk type>`.
+    /// Creates a new `ChunkStore` at location `root/CHUNK_STORE_DIR/<chunk type>` for each of the
+    /// `roots`.
     ///
     /// If the location specified already exists, the previous ChunkStore there is opened, otherwise
-    /// the required folder structure is created.
+    /// the required folder structure is created.  If some, but not all, of the locations can't be
+    /// opened, the store carries on without them.
+    ///
+    /// The backend of each location is opened with `options`.  For the default `FsBackend`, these
+    /// are the number of levels of subdirectories to spread chunk files over, named by the hex
+    /// prefix of a hash of the chunk id.  A store previously written with a different depth
+    /// (including the legacy flat layout) is migrated to the requested one.
     ///
     /// The maximum storage space is defined by `max_capacity`.  This specifies the max usable by
     /// _all_ `ChunkStores`, not per `ChunkStore`.
-    pub async fn new<P: AsRef<Path>>(root: P, used_space: UsedSpace) -> Result<Self> {
-        let dir = root.as_ref().join(CHUNK_STORE_DIR).join(Self::subdir());
+    ///
+    /// The recorded used space of an existing store is checked against the sizes of its chunks,
+    /// and corrected if they differ.
+    pub async fn new<P: AsRef<Path>>(
+        roots: &[P],
+        options: B::Options,
+        used_space: UsedSpace,
+    ) -> Result<Self> {
+        let (chunk_store, _) = Self::open(roots, options, used_space).await?;
+        Ok(chunk_store)
+    }
 
-        if fs::read(&dir).is_ok() {
-            trace!("Loading ChunkStore at {}", dir.display());
-        } else {
-            Self::create_new_root(&dir)?
+    /// As `new`, also returning the reports of reconciling the used space of the store.
+    async fn open<P: AsRef<Path>>(
+        roots: &[P],
+        options: B::Options,
+        used_space: UsedSpace,
+    ) -> Result<(Self, Vec<UsedSpaceReport>)> {
+        let mut volumes = Vec::new();
+        let mut reports = Vec::new();
+        let mut last_error = None;
+        for root in roots {
+            let dir = root.as_ref().join(CHUNK_STORE_DIR).join(Self::subdir());
+            match Volume::open(dir.clone(), options.clone(), &used_space).await {
+                Ok((volume, report)) => {
+                    volumes.push(volume);
+                    reports.push(report);
+                }
+                Err(error) => {
+                    error!("Could not open ChunkStore at {}: {}", dir.display(), error);
+                    last_error = Some(error);
+                }
+            }
         }
-
-        let id = used_space.add_local_store(&dir).await?;
-        Ok(ChunkStore {
-            dir,
+        if volumes.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| Error::Logic("No root dir for ChunkStore".to_string())));
+        }
+        let chunk_store = ChunkStore {
+            volumes,
+            cache: None,
+            encryption: None,
             used_space,
-            id,
+            counters: Mutex::new(OpCounters::default()),
             _phantom: PhantomData,
-        })
+        };
+        Ok((chunk_store, reports))
+    }
+
+    /// Opens the `ChunkStore` at `root`, if there is one, to reconcile its used space.
+    async fn reconcile_at(root: &Path, options: B::Options) -> Result<Option<UsedSpaceReport>> {
+        if !root.join(CHUNK_STORE_DIR).join(Self::subdir()).is_dir() {
+            return Ok(None);
+        }
+        let (_, reports) = Self::open(&[root], options, UsedSpace::new(u64::MAX)).await?;
+        Ok(reports.into_iter().next())
     }
 }
 
-impl<T: Chunk> ChunkStore<T> {
-    fn create_new_root(root: &Path) -> Result<()> {
-        trace!("Creating ChunkStore at {}", root.display());
-        fs::create_dir_all(root)?;
+impl<T: Chunk, B: Backend> ChunkStore<T, B> {
+    /// Keeps up to `capacity` bytes of the most recently read chunks in memory, serving repeated
+    /// reads of them without going to the backend.  A `capacity` of 0 disables the cache.
+    pub fn with_cache(mut self, capacity: u64) -> Self {
+        self.cache = if capacity > 0 {
+            Some(Mutex::new(ChunkCache::new(capacity)))
+        } else {
+            None
+        };
+        self
+    }
 
-        // Verify that chunk files can be created.
-        let temp_file_path = root.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
-        let _ = File::create(&temp_file_path)?;
-        fs::remove_file(temp_file_path)?;
+    /// Encrypts chunks with `key` before writing them to the backend, first encrypting any chunks
+    /// stored before encryption was enabled.  Fails with `Error::Encryption` if the store has been
+    /// encrypted, but `key` is `None`.
+    pub async fn with_encryption(mut self, key: Option<EncryptionKey>) -> Result<Self> {
+        for volume in &self.volumes {
+            match &key {
+                Some(key) => volume.encrypt(key.clone(), &self.used_space).await?,
+                None if volume.is_encrypted() => return Err(Error::Encryption),
+                None => (),
+            }
+        }
+        self.encryption = key;
+        Ok(self)
+    }
 
-        Ok(())
+    /// Limits the space this store can use to `quota`, on top of the max capacity shared with the
+    /// other stores.  `None` leaves it only limited by the latter.
+    pub async fn with_quota(self, quota: Option<u64>) -> Result<Self> {
+        for volume in &self.volumes {
+            self.used_space.set_quota(volume.id, quota).await?;
+        }
+        Ok(self)
     }
 
-    /// Stores a new data chunk.
+    /// The hit and miss counters of the cache, if enabled.
+    pub async fn cache_stats(&self) -> Option<CacheStats> {
+        match &self.cache {
+            Some(cache) => Some(cache.lock().await.stats()),
+            None => None,
+        }
+    }
+
+    /// Stores a new data chunk, in the volume with the most space available.
     ///
     /// If there is not enough storage space available, returns `Error::NotEnoughSpace`.  In case of
     /// an IO error, it returns `Error::Io`.
     ///
-    /// If a chunk with the same id already exists, it will be overwritten.
+    /// If a chunk with the same id already exists, it will be overwritten, in the volume holding
+    /// it.  The old chunk is kept if the new one can't be written.
     pub async fn put(&mut self, chunk: &T) -> Result<()> {
         info!("Writing chunk");
-        let serialised_chunk = utils::serialise(chunk)?;
+        let serialised_chunk = match &self.encryption {
+            Some(encryption_key) => encryption_key.encrypt(&utils::serialise(chunk)?)?,
+            None => utils::serialise(chunk)?.to_vec(),
+        };
         let consumed_space = serialised_chunk.len() as u64;
 
         info!("consumed space: {:?}", consumed_space);
         info!("max : {:?}", self.used_space.max_capacity().await);
         info!("use space total : {:?}", self.used_space.total().await);
 
-        let file_path = self.file_path(chunk.id())?;
-        let _ = self.do_delete(&file_path).await;
-
-        // pre-reserve space
-        self.used_space.increase(self.id, consumed_space).await?;
+        let key = utils::serialise(chunk.id())?;
+        self.uncache(&key).await;
+
+        // An existing chunk is replaced where it is, by the backend writing over it, so that it's
+        // only lost once the new one has been written in full.
+        let volume = match self.holder(&key).await {
+            Some(volume) => volume,
+            None => self.emptiest_volume().await,
+        };
+        let backend_key = key.to_vec();
+        let replaced_space = volume
+            .run(move |backend| Ok(backend.size(&backend_key).unwrap_or(0)))
+            .await?;
+        // pre-reserve the extra space, if any
+        if consumed_space > replaced_space {
+            self.used_space
+                .increase(volume.id, consumed_space - replaced_space)
+                .await?;
+        }
         trace!(
             "use space total after add: {:?}",
             self.used_space.total().await
         );
 
-        let res = File::create(&file_path).and_then(|mut file| {
-            file.write_all(&serialised_chunk)?;
-            file.sync_all()
-        });
-
-        match res {
-            Ok(_) => {
+        let backend_key = key.clone();
+        let result = volume
+            .run(move |backend| backend.put(&backend_key, &serialised_chunk))
+            .await;
+        match result {
+            Ok(()) => {
+                if replaced_space > consumed_space {
+                    self.used_space
+                        .decrease(volume.id, replaced_space - consumed_space)
+                        .await?;
+                }
+                let _ = volume.index.lock().await.insert(key.to_vec());
                 info!("Writing chunk succeeded!");
+                let mut counters = self.counters.lock().await;
+                counters.writes += 1;
+                counters.last_write = Some(SystemTime::now());
                 Ok(())
             }
             Err(e) => {
                 info!("Writing chunk failed!");
-                self.used_space.decrease(self.id, consumed_space).await?;
-                Err(e.into())
+                if consumed_space > replaced_space {
+                    self.used_space
+                        .decrease(volume.id, consumed_space - replaced_space)
+                        .await?;
+                }
+                Err(e)
             }
         }
     }
@@ -141,103 +322,434 @@ impl<T: Chunk> ChunkStore<T> {
     /// If the data doesn't exist, it does nothing and returns `Ok`.  In the case of an IO error, it
     /// returns `Error::Io`.
     pub async fn delete(&mut self, id: &T::Id) -> Result<()> {
-        self.do_delete(&self.file_path(id)?).await
+        let key = utils::serialise(id)?;
+        self.uncache(&key).await;
+        self.do_delete(&key).await?;
+        self.counters.lock().await.deletes += 1;
+        Ok(())
     }
 
-    /// Used space to max space ratio.
+    /// Used space to max space ratio, taking into account the quota of the store and the
+    /// capacities of its volumes.  For a store spread over several volumes, that of the emptiest.
     pub async fn used_space_ratio(&self) -> f64 {
         let used = self.total_used_space().await;
         let total = self.used_space.max_capacity().await;
-        let used_space_ratio = used as f64 / total as f64;
         info!("Used space: {:?}", used);
         info!("Total space: {:?}", total);
+        let mut used_space_ratio = f64::MAX;
+        for volume in &self.volumes {
+            used_space_ratio = used_space_ratio.min(self.used_space.used_ratio(volume.id).await);
+        }
         info!("Used space ratio: {:?}", used_space_ratio);
         used_space_ratio
     }
 
     /// Returns a data chunk previously stored under `id`.
     ///
-    /// If the data file can't be accessed, it returns `Error::NoSuchChunk`.
-    pub fn get(&self, id: &T::Id) -> Result<T> {
-        let mut file = File::open(self.file_path(id)?).map_err(|_| Error::NoSuchChunk)?;
-        let mut contents = vec![];
-        let _ = file.read_to_end(&mut contents)?;
+    /// If the data can't be accessed, it returns `Error::NoSuchChunk`.
+    pub async fn get(&self, id: &T::Id) -> Result<T> {
+        let chunk = self.fetch(id).await?;
+        self.counters.lock().await.reads += 1;
+        Ok(chunk)
+    }
+
+    /// Reports what the store holds, walking all its keys, and the operations it has served.
+    pub async fn stats(&self) -> Result<ChunkStoreStats> {
+        let mut stats = ChunkStoreStats::new(*self.counters.lock().await);
+        for volume in &self.volumes {
+            let mut after = None;
+            loop {
+                let batch = volume
+                    .run(move |backend| {
+                        Ok(backend
+                            .keys_after(after.as_deref(), STATS_BATCH_SIZE)
+                            .into_iter()
+                            .map(|key| {
+                                let size = backend.size(&key).unwrap_or(0);
+                                (key, size)
+                            })
+                            .collect::<Vec<_>>())
+                    })
+                    .await?;
+                let is_last_batch = batch.len() < STATS_BATCH_SIZE;
+                after = batch.last().map(|(key, _)| key.clone());
+                for (key, size) in batch {
+                    if let Some(id) = to_chunk_id::<T::Id>(&key) {
+                        stats.add_chunk(id.name(), size);
+                    }
+                }
+                if is_last_batch {
+                    break;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Returns the chunk stored under `id`, from the cache if it's there.
+    async fn fetch(&self, id: &T::Id) -> Result<T> {
+        let key = utils::serialise(id)?;
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.read_chunk(id, &key).await.map(|(chunk, _)| chunk),
+        };
+        if let Some(chunk) = cache.lock().await.get(&key) {
+            return Ok(chunk);
+        }
+        let (chunk, size) = self.read_chunk(id, &key).await?;
+        cache.lock().await.insert(key.to_vec(), chunk.clone(), size);
+        Ok(chunk)
+    }
+
+    /// Re-reads the data chunk stored under `id`, and checks that it still deserialises to a chunk
+    /// with that id.  For content-addressed chunks, such as `Blob`s, the id is recomputed from the
+    /// contents when deserialising, so this re-hashes the chunk against its address.
+    ///
+    /// If the data can't be accessed, it returns `Error::NoSuchChunk`.
+    pub async fn verify(&self, id: &T::Id) -> Result<bool> {
+        let key = utils::serialise(id)?;
+        let volume = self.holder(&key).await.ok_or(Error::NoSuchChunk)?;
+        let contents = match self.read(volume, &key).await {
+            Ok(contents) => contents,
+            Err(Error::Encryption) => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        Ok(match bincode::deserialize::<T>(&contents) {
+            Ok(chunk) => chunk.id() == id,
+            Err(_) => false,
+        })
+    }
+
+    /// Moves the data chunk stored under `id` out of the store (for the `FsBackend`, into the
+    /// quarantine dir of its volume), releasing the space it used.
+    ///
+    /// If the data can't be accessed, it returns `Error::NoSuchChunk`.
+    pub async fn quarantine(&mut self, id: &T::Id) -> Result<()> {
+        let key = utils::serialise(id)?;
+        self.uncache(&key).await;
+        let volume = self.holder(&key).await.ok_or(Error::NoSuchChunk)?;
+        let _ = volume.index.lock().await.remove(&key[..]);
+        let key = key.to_vec();
+        let size = volume
+            .run(move |backend| {
+                let size = backend.size(&key).ok_or(Error::NoSuchChunk)?;
+                backend.quarantine(&key)?;
+                Ok(size)
+            })
+            .await?;
+        self.used_space.decrease(volume.id, size).await
+    }
+
+    pub async fn total_used_space(&self) -> u64 {
+        self.used_space.total().await
+    }
+
+    /// Tests if a data chunk has been previously stored under `id`.  Answered from the in-memory
+    /// index of the stored keys, without going to the backend.
+    pub async fn has(&self, id: &T::Id) -> bool {
+        match utils::serialise(id) {
+            Ok(key) => self.holder(&key).await.is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Lists all keys of currently stored data.  Prefer `keys_page` for walking large stores.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub async fn keys(&self) -> Vec<T::Id> {
+        let mut keys = Vec::new();
+        for volume in &self.volumes {
+            match volume.run(|backend| Ok(backend.keys())).await {
+                Ok(volume_keys) => keys.extend(volume_keys),
+                Err(error) => warn!(
+                    "Could not list chunks in {}: {}",
+                    volume.dir.display(),
+                    error
+                ),
+            }
+        }
+        keys.iter().filter_map(|key| to_chunk_id(key)).collect()
+    }
+
+    /// Lists up to `limit` keys of currently stored data, whose names match `prefix` if given,
+    /// starting from `cursor` (or from the start if `None`).  Pass the returned page's `next`
+    /// cursor to continue from where it ends; it is `None` once all keys have been listed.
+    ///
+    /// Keys are read from disk a page at a time, so walking a large store this way doesn't hold all
+    /// of them in memory.  Data stored or deleted while walking may or may not be listed.
+    pub async fn keys_page(
+        &self,
+        cursor: Option<KeysCursor>,
+        limit: usize,
+        prefix: Option<&Prefix>,
+    ) -> Result<KeysPage<T::Id>> {
+        let KeysCursor {
+            mut volume,
+            mut last_key,
+        } = cursor.unwrap_or_default();
+        let mut keys = Vec::new();
+        while volume < self.volumes.len() && keys.len() < limit {
+            let batch = limit - keys.len();
+            let after = last_key.clone();
+            let page = self.volumes[volume]
+                .run(move |backend| Ok(backend.keys_after(after.as_deref(), batch)))
+                .await?;
+            let is_last_page = page.len() < batch;
+            for key in page {
+                match (to_chunk_id::<T::Id>(&key), prefix) {
+                    (Some(id), Some(prefix)) if !prefix.matches(&id.name()) => (),
+                    (Some(id), _) => keys.push(id),
+                    (None, _) => (),
+                }
+                last_key = Some(key);
+            }
+            if is_last_page {
+                volume += 1;
+                last_key = None;
+            }
+        }
+        let next = if volume < self.volumes.len() {
+            Some(KeysCursor { volume, last_key })
+        } else {
+            None
+        };
+        Ok(KeysPage { keys, next })
+    }
+
+    /// Deletes the chunk stored under `key` from whichever volumes hold it.
+    async fn do_delete(&mut self, key: &[u8]) -> Result<()> {
+        for volume in &self.volumes {
+            if !volume.index.lock().await.remove(key) {
+                continue;
+            }
+            let key = key.to_vec();
+            let released = volume
+                .run(move |backend| match backend.size(&key) {
+                    Some(size) => backend.delete(&key).map(|()| size),
+                    None => Ok(0),
+                })
+                .await?;
+            self.used_space.decrease(volume.id, released).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads and deserialises the chunk stored under `key`, also returning its serialised size.
+    async fn read_chunk(&self, id: &T::Id, key: &[u8]) -> Result<(T, u64)> {
+        let volume = self.holder(key).await.ok_or(Error::NoSuchChunk)?;
+        let contents = self.read(volume, key).await?;
         let chunk = bincode::deserialize::<T>(&contents)?;
         // Check it's the requested chunk variant.
         if chunk.id() == id {
-            Ok(chunk)
+            Ok((chunk, contents.len() as u64))
         } else {
             Err(Error::NoSuchChunk)
         }
     }
 
-    pub async fn total_used_space(&self) -> u64 {
-        self.used_space.total().await
+    /// Reads the serialised chunk stored under `key` in `volume`, decrypting it if need be.
+    async fn read(&self, volume: &Volume<B>, key: &[u8]) -> Result<Vec<u8>> {
+        let contents = volume.read(key).await?;
+        match &self.encryption {
+            Some(encryption_key) => encryption_key.decrypt(&contents),
+            None => Ok(contents),
+        }
+    }
+
+    /// The volume holding the chunk stored under `key`, if any.
+    async fn holder(&self, key: &[u8]) -> Option<&Volume<B>> {
+        for volume in &self.volumes {
+            if volume.index.lock().await.contains(key) {
+                return Some(volume);
+            }
+        }
+        None
+    }
+
+    /// The volume new chunks are put in: the one with the most space available, or the first of
+    /// those if several have the same.
+    async fn emptiest_volume(&self) -> &Volume<B> {
+        let mut emptiest = &self.volumes[0];
+        let mut most_available = self.used_space.available(emptiest.id).await;
+        for volume in &self.volumes[1..] {
+            let available = self.used_space.available(volume.id).await;
+            if available > most_available {
+                emptiest = volume;
+                most_available = available;
+            }
+        }
+        emptiest
+    }
+
+    async fn uncache(&self, key: &[u8]) {
+        if let Some(cache) = &self.cache {
+            cache.lock().await.remove(key);
+        }
+    }
+}
+
+impl<B: Backend> Volume<B> {
+    /// Opens the backend at `dir`, and reconciles its used space.
+    async fn open(
+        dir: PathBuf,
+        options: B::Options,
+        used_space: &UsedSpace,
+    ) -> Result<(Self, UsedSpaceReport)> {
+        let backend_dir = dir.clone();
+        let backend = task::spawn_blocking(move || B::open(&backend_dir, options))
+            .await
+            .map_err(io::Error::from)??;
+
+        let id = match backend.record_dir() {
+            Some(record_dir) => used_space.add_local_store(record_dir).await?,
+            None => used_space.add_in_memory_store().await,
+        };
+        let volume = Self {
+            dir,
+            backend: Arc::new(backend),
+            io_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_IO)),
+            id,
+            index: Mutex::new(HashSet::new()),
+        };
+        let report = volume.reconcile_used_space(used_space).await?;
+        Ok((volume, report))
     }
 
-    /// Tests if a data chunk has been previously stored under `id`.
-    pub fn has(&self, id: &T::Id) -> bool {
-        if let Ok(path) = self.file_path(id) {
-            fs::metadata(path)
-                .as_ref()
-                .map(Metadata::is_file)
-                .unwrap_or(false)
+    /// Sums the sizes of the stored chunks, and corrects the recorded used space of the volume
+    /// with it.  Also rebuilds the index of the stored keys.
+    async fn reconcile_used_space(&self, used_space: &UsedSpace) -> Result<UsedSpaceReport> {
+        let (index, actual) = self
+            .run(|backend| {
+                let mut index = HashSet::new();
+                let mut actual = 0;
+                for key in backend.keys() {
+                    if let Some(size) = backend.size(&key) {
+                        actual += size;
+                        let _ = index.insert(key);
+                    }
+                }
+                Ok((index, actual))
+            })
+            .await?;
+        let chunk_count = index.len() as u64;
+        *self.index.lock().await = index;
+        let recorded = used_space.reconcile(self.id, actual).await?;
+        let report = UsedSpaceReport {
+            dir: self.dir.clone(),
+            chunk_count,
+            recorded,
+            actual,
+        };
+        if report.is_consistent() {
+            trace!("Used space is consistent: {}", report);
         } else {
-            false
+            warn!("Used space record was inconsistent: {}", report);
         }
+        Ok(report)
     }
 
-    /// Lists all keys of currently stored data.
-    #[cfg_attr(not(test), allow(unused))]
-    pub fn keys(&self) -> Vec<T::Id> {
-        fs::read_dir(&self.dir)
-            .map(|entries| {
-                entries
-                    .filter_map(|entry| to_chunk_id(&entry.ok()?))
-                    .collect()
+    /// Encrypts the chunks stored in plaintext, from before encryption was enabled, then records
+    /// that the volume is encrypted.
+    async fn encrypt(&self, encryption_key: EncryptionKey, used_space: &UsedSpace) -> Result<()> {
+        if self.is_encrypted() {
+            return Ok(());
+        }
+        let count = self
+            .run(move |backend| {
+                let mut count = 0;
+                for key in backend.keys() {
+                    let contents = backend.get(&key)?;
+                    // Skips those encrypted before an interrupted migration.
+                    if encryption_key.decrypt(&contents).is_err() {
+                        backend.put(&key, &encryption_key.encrypt(&contents)?)?;
+                        count += 1;
+                    }
+                }
+                if let Some(record_dir) = backend.record_dir() {
+                    fs::write(record_dir.join(ENCRYPTED_FILENAME), [])?;
+                }
+                Ok(count)
             })
-            .unwrap_or_else(|_| Vec::new())
+            .await?;
+        if count > 0 {
+            info!("Encrypted {} chunks in {}", count, self.dir.display());
+            let _ = self.reconcile_used_space(used_space).await?;
+        }
+        Ok(())
     }
 
-    async fn do_delete(&mut self, file_path: &Path) -> Result<()> {
-        if let Ok(metadata) = fs::metadata(file_path) {
-            self.used_space.decrease(self.id, metadata.len()).await?;
-            fs::remove_file(file_path).map_err(From::from)
-        } else {
-            Ok(())
+    /// Whether all chunks of the volume have been encrypted.
+    fn is_encrypted(&self) -> bool {
+        match self.backend.record_dir() {
+            Some(record_dir) => record_dir.join(ENCRYPTED_FILENAME).is_file(),
+            None => false,
         }
     }
 
-    fn file_path(&self, id: &T::Id) -> Result<PathBuf> {
-        Ok(self.dir.join(&hex::encode(utils::serialise(id)?)))
+    async fn read(&self, key: &[u8]) -> Result<Vec<u8>> {
+        let key = key.to_vec();
+        self.run(move |backend| backend.get(&key)).await
+    }
+
+    /// Runs `op` against the backend on the blocking thread pool, once one of the
+    /// `MAX_CONCURRENT_IO` permits of the volume is available.
+    async fn run<F, R>(&self, op: F) -> Result<R>
+    where
+        F: FnOnce(&B) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let _permit = self.io_permits.acquire().await;
+        let backend = self.backend.clone();
+        task::spawn_blocking(move || op(&backend))
+            .await
+            .map_err(io::Error::from)?
+    }
+}
+
+/// Reconciles the recorded used space of each of the `ChunkStore`s at each of the `roots` with
+/// the sizes of the chunks actually stored, correcting the records where they differ.
+///
+/// The stores are opened with the `backend` they are kept in, and for the `Fs` one, `dir_depth`.
+pub async fn reconcile_used_space<P: AsRef<Path>>(
+    roots: &[P],
+    backend: ChunkBackend,
+    dir_depth: usize,
+) -> Result<Vec<UsedSpaceReport>> {
+    let options = BackendOptions {
+        kind: backend,
+        dir_depth,
+    };
+    let mut reports = Vec::new();
+    for root in roots {
+        let root = root.as_ref();
+        reports.push(BlobChunkStore::reconcile_at(root, options).await?);
+        reports.push(MapChunkStore::reconcile_at(root, options).await?);
+        reports.push(SequenceChunkStore::reconcile_at(root, options).await?);
     }
+    Ok(reports.into_iter().flatten().collect())
 }
 
 pub(crate) trait Subdir {
     fn subdir() -> &'static Path;
 }
 
-impl Subdir for BlobChunkStore {
+impl<B: Backend> Subdir for ChunkStore<Blob, B> {
     fn subdir() -> &'static Path {
         Path::new("immutable")
     }
 }
 
-impl Subdir for MapChunkStore {
+impl<B: Backend> Subdir for ChunkStore<Map, B> {
     fn subdir() -> &'static Path {
         Path::new("mutable")
     }
 }
 
-impl Subdir for SequenceChunkStore {
+impl<B: Backend> Subdir for ChunkStore<Sequence, B> {
     fn subdir() -> &'static Path {
         Path::new("sequence")
     }
 }
 
-fn to_chunk_id<T: ChunkId>(entry: &DirEntry) -> Option<T> {
-    let file_name = entry.file_name();
-    let file_name = file_name.into_string().ok()?;
-    let bytes = hex::decode(file_name).ok()?;
-    bincode::deserialize(&bytes).ok()
+fn to_chunk_id<T: ChunkId>(key: &[u8]) -> Option<T> {
+    bincode::deserialize(key).ok()
 }
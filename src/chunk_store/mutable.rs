@@ -8,6 +8,7 @@
 
 use super::chunk::{Chunk, ChunkId};
 use sn_data_types::{Map, MapAddress};
+use xor_name::XorName;
 
 impl Chunk for Map {
     type Id = MapAddress;
@@ -19,4 +20,8 @@ impl Chunk for Map {
     }
 }
 
-impl ChunkId for MapAddress {}
+impl ChunkId for MapAddress {
+    fn name(&self) -> XorName {
+        *self.name()
+    }
+}
@@ -8,6 +8,7 @@
 
 use super::chunk::{Chunk, ChunkId};
 use sn_data_types::{Sequence, SequenceAddress};
+use xor_name::XorName;
 
 impl Chunk for Sequence {
     type Id = SequenceAddress;
@@ -16,4 +17,8 @@ impl Chunk for Sequence {
     }
 }
 
-impl ChunkId for SequenceAddress {}
+impl ChunkId for SequenceAddress {
+    fn name(&self) -> XorName {
+        *self.name()
+    }
+}
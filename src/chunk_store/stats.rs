@@ -0,0 +1,139 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::UsedSpaceStats;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::SystemTime,
+};
+use xor_name::XorName;
+
+/// Upper bounds (inclusive) of the buckets of the chunk size histogram, in bytes.  Chunks larger
+/// than the last are counted in an extra, unbounded, bucket.
+const SIZE_BUCKETS: [u64; 6] = [
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    256 * 1024,
+    1024 * 1024,
+];
+
+/// Number of the largest chunks listed in `ChunkStoreStats`.
+const LARGEST_CHUNKS_COUNT: usize = 10;
+
+/// What a `ChunkStore` holds, and the operations it has served since it was opened.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkStoreStats {
+    /// Number of chunks stored.
+    pub chunk_count: u64,
+    /// Space taken by the stored chunks, in bytes as stored (i.e. serialised, and encrypted if
+    /// encryption at rest is enabled).
+    pub total_bytes: u64,
+    /// Number of stored chunks by size.
+    pub size_histogram: Vec<SizeBucket>,
+    /// Names and sizes of the largest chunks stored, largest first.
+    pub largest: Vec<(XorName, u64)>,
+    /// Number of chunks written.
+    pub writes: u64,
+    /// Number of chunks read.
+    pub reads: u64,
+    /// Number of chunks deleted.
+    pub deletes: u64,
+    /// When a chunk was last written, if any has been.
+    pub last_write: Option<SystemTime>,
+}
+
+/// A bucket of the chunk size histogram of a `ChunkStoreStats`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SizeBucket {
+    /// The largest size, in bytes, counted in this bucket, or `None` for the last one.
+    pub max_size: Option<u64>,
+    /// Number of chunks of sizes within this bucket.
+    pub count: u64,
+}
+
+/// Counters of the operations served by a `ChunkStore`.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct OpCounters {
+    pub writes: u64,
+    pub reads: u64,
+    pub deletes: u64,
+    pub last_write: Option<SystemTime>,
+}
+
+impl ChunkStoreStats {
+    /// Stats of an empty store, which has served the operations counted by `counters`.
+    pub(super) fn new(counters: OpCounters) -> Self {
+        let size_histogram = SIZE_BUCKETS
+            .iter()
+            .map(|max_size| Some(*max_size))
+            .chain(std::iter::once(None))
+            .map(|max_size| SizeBucket { max_size, count: 0 })
+            .collect();
+        Self {
+            chunk_count: 0,
+            total_bytes: 0,
+            size_histogram,
+            largest: Vec::new(),
+            writes: counters.writes,
+            reads: counters.reads,
+            deletes: counters.deletes,
+            last_write: counters.last_write,
+        }
+    }
+
+    /// Counts a stored chunk of `size` bytes.
+    pub(super) fn add_chunk(&mut self, name: XorName, size: u64) {
+        self.chunk_count += 1;
+        self.total_bytes += size;
+        if let Some(bucket) = self
+            .size_histogram
+            .iter_mut()
+            .find(|bucket| match bucket.max_size {
+                Some(max_size) => size <= max_size,
+                None => true,
+            })
+        {
+            bucket.count += 1;
+        }
+        let position = self
+            .largest
+            .iter()
+            .position(|(_, other_size)| size > *other_size)
+            .unwrap_or(self.largest.len());
+        if position < LARGEST_CHUNKS_COUNT {
+            self.largest.insert(position, (name, size));
+            self.largest.truncate(LARGEST_CHUNKS_COUNT);
+        }
+    }
+}
+
+impl Display for ChunkStoreStats {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} chunks, {} bytes, {} writes, {} reads, {} deletes",
+            self.chunk_count, self.total_bytes, self.writes, self.reads, self.deletes
+        )
+    }
+}
+
+/// The stats of the stores of a node, by data type, as reported by its Adult or Elder duties.
+/// Adults only store blobs, and Elders only maps and sequences.
+#[derive(Clone, Debug)]
+pub struct StorageStats {
+    /// Stats of the blob store, if kept.
+    pub blobs: Option<ChunkStoreStats>,
+    /// Stats of the map store, if kept.
+    pub maps: Option<ChunkStoreStats>,
+    /// Stats of the sequence store, if kept.
+    pub sequences: Option<ChunkStoreStats>,
+    /// The space used by all the stores of the node.
+    pub used_space: UsedSpaceStats,
+}
@@ -7,16 +7,22 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    backend::{chunk_files, Backend},
     chunk::{Chunk, ChunkId},
-    ChunkStore, Result as ChunkStoreResult, Subdir, UsedSpace,
+    BackendOptions, ChunkBackend, ChunkStore, ConfiguredBackend, FsBackend, KvBackend,
+    MemoryBackend, Result as ChunkStoreResult, Subdir, UsedSpace,
 };
-use crate::{Error, Result, ToDbKey};
+use crate::{encryption::EncryptionKey, utils, Error, Result, ToDbKey};
 use rand::{distributions::Standard, rngs::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
+use sn_data_types::{Blob, PublicBlob};
 use std::{path::Path, u64};
 use tempdir::TempDir;
+use xor_name::{Prefix, XorName};
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+const DIR_DEPTH: usize = 2;
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 struct Data {
     id: Id,
     value: Vec<u8>,
@@ -34,9 +40,15 @@ impl Chunk for Data {
 struct Id(u64);
 
 impl ToDbKey for Id {}
-impl ChunkId for Id {}
+impl ChunkId for Id {
+    fn name(&self) -> XorName {
+        let mut name = [0; 32];
+        name[..8].copy_from_slice(&self.0.to_le_bytes());
+        XorName(name)
+    }
+}
 
-impl Subdir for ChunkStore<Data> {
+impl<B: Backend> Subdir for ChunkStore<Data, B> {
     fn subdir() -> &'static Path {
         Path::new("test")
     }
@@ -51,6 +63,12 @@ fn temp_dir() -> ChunkStoreResult<TempDir> {
     TempDir::new("test").map_err(|e| Error::TempDirCreationFailed(e.to_string()))
 }
 
+// The id of the chunk a file of the `FsBackend` holds, going by its name.
+fn file_chunk_id(path: &Path) -> Option<Id> {
+    let key = hex::decode(path.file_name()?.to_str()?).ok()?;
+    super::to_chunk_id(&key)
+}
+
 struct Chunks {
     data_and_sizes: Vec<(Vec<u8>, u64)>,
     total_size: u64,
@@ -87,7 +105,8 @@ async fn successful_put() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::<Data>::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate().rev() {
         let the_data = &Data {
@@ -95,17 +114,17 @@ async fn successful_put() -> Result<()> {
             value: data.clone(),
         };
         let used_space_before = chunk_store.total_used_space().await;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         chunk_store.put(the_data).await?;
         let used_space_after = chunk_store.total_used_space().await;
         assert_eq!(used_space_after, used_space_before + size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         assert!(used_space_after <= chunks.total_size);
     }
 
     assert_eq!(chunk_store.total_used_space().await, chunks.total_size);
 
-    let mut keys = chunk_store.keys();
+    let mut keys = chunk_store.keys().await;
     keys.sort();
     assert_eq!(
         (0..chunks.data_and_sizes.len())
@@ -123,7 +142,8 @@ async fn failed_put_when_not_enough_space() -> Result<()> {
     let root = temp_dir()?;
     let capacity = 32;
     let used_space = UsedSpace::new(capacity);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     let data = Data {
         id: Id(rng.gen()),
@@ -135,7 +155,7 @@ async fn failed_put_when_not_enough_space() -> Result<()> {
 
     match chunk_store.put(&data).await {
         Err(Error::NotEnoughSpace) => (),
-        x => return Err(crate::Error::Logic(format!("Unexpected: {:?}", x))),
+        x => return Err(Error::Logic(format!("Unexpected: {:?}", x))),
     }
 
     Ok(())
@@ -148,7 +168,8 @@ async fn delete() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate() {
         let the_data = &Data {
@@ -157,9 +178,9 @@ async fn delete() -> Result<()> {
         };
         chunk_store.put(the_data).await?;
         assert_eq!(chunk_store.total_used_space().await, *size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         chunk_store.delete(&the_data.id).await?;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         assert_eq!(chunk_store.total_used_space().await, 0);
     }
 
@@ -173,7 +194,8 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         chunk_store
@@ -185,7 +207,7 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
     }
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
-        let retrieved_value = chunk_store.get(&Id(index as u64))?;
+        let retrieved_value = chunk_store.get(&Id(index as u64)).await?;
         assert_eq!(*data, retrieved_value.value);
     }
 
@@ -199,7 +221,8 @@ async fn overwrite_value() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     for (data, size) in chunks.data_and_sizes {
         chunk_store
@@ -209,23 +232,54 @@ async fn overwrite_value() -> Result<()> {
             })
             .await?;
         assert_eq!(chunk_store.total_used_space().await, size);
-        let retrieved_data = chunk_store.get(&Id(0))?;
+        let retrieved_data = chunk_store.get(&Id(0)).await?;
         assert_eq!(data, retrieved_data.value);
     }
 
     Ok(())
 }
 
+#[tokio::test]
+async fn failed_overwrite_keeps_the_old_value() -> Result<()> {
+    let rng = new_rng();
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(64);
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
+
+    let old_data = Data {
+        id: Id(0),
+        value: rng.sample_iter(&Standard).take(16).collect(),
+    };
+    chunk_store.put(&old_data).await?;
+    let used_space_before = chunk_store.total_used_space().await;
+
+    let new_data = Data {
+        id: Id(0),
+        value: rng.sample_iter(&Standard).take(64).collect(),
+    };
+    match chunk_store.put(&new_data).await {
+        Err(Error::NotEnoughSpace) => (),
+        x => return Err(Error::Logic(format!("Unexpected: {:?}", x))),
+    }
+
+    assert_eq!(chunk_store.get(&Id(0)).await?, old_data);
+    assert_eq!(chunk_store.total_used_space().await, used_space_before);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn get_fails_when_key_does_not_exist() -> Result<()> {
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let chunk_store: ChunkStore<Data> = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let chunk_store: ChunkStore<Data> =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     let id = Id(new_rng().gen());
-    match chunk_store.get(&id) {
+    match chunk_store.get(&id).await {
         Err(Error::NoSuchChunk) => (),
-        x => return Err(crate::Error::Logic(format!("Unexpected {:?}", x))),
+        x => return Err(Error::Logic(format!("Unexpected {:?}", x))),
     }
 
     Ok(())
@@ -238,11 +292,12 @@ async fn keys() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
-        assert!(!chunk_store.keys().contains(&id));
+        assert!(!chunk_store.keys().await.contains(&id));
         chunk_store
             .put(&Data {
                 id,
@@ -250,7 +305,7 @@ async fn keys() -> Result<()> {
             })
             .await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await;
         assert!(keys.contains(&id));
         assert_eq!(keys.len(), index + 1);
     }
@@ -258,13 +313,667 @@ async fn keys() -> Result<()> {
     for (index, _) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
 
-        assert!(chunk_store.keys().contains(&id));
+        assert!(chunk_store.keys().await.contains(&id));
         chunk_store.delete(&id).await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await;
         assert!(!keys.contains(&id));
         assert_eq!(keys.len(), chunks.data_and_sizes.len() - index - 1);
     }
 
     Ok(())
 }
+
+#[tokio::test]
+async fn keys_are_paginated() -> Result<()> {
+    const CHUNK_COUNT: u64 = 25;
+    const PAGE_SIZE: usize = 4;
+    let rng = new_rng();
+    let first_root = temp_dir()?;
+    let second_root = temp_dir()?;
+    let roots = [first_root.path(), second_root.path()];
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&roots, DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    for index in 0..CHUNK_COUNT {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: rng.sample_iter(&Standard).take(32).collect(),
+            })
+            .await?;
+    }
+
+    let mut listed = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = chunk_store.keys_page(cursor, PAGE_SIZE, None).await?;
+        assert!(page.keys.len() <= PAGE_SIZE);
+        listed.extend(page.keys);
+        // Deleting the last listed chunk doesn't lose our place.
+        if let Some(id) = listed.last() {
+            chunk_store.delete(id).await?;
+        }
+        match page.next {
+            // Resumable from a persisted cursor.
+            Some(next) => cursor = Some(utils::deserialise(&utils::serialise(&next)?)?),
+            None => break,
+        }
+    }
+    listed.sort();
+    assert_eq!((0..CHUNK_COUNT).map(Id).collect::<Vec<_>>(), listed);
+
+    // Only the keys whose names match the prefix are listed.
+    for index in 0..CHUNK_COUNT {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: vec![],
+            })
+            .await?;
+    }
+    let prefix = Prefix::new(7, XorName([4; 32]));
+    let page = chunk_store
+        .keys_page(None, CHUNK_COUNT as usize, Some(&prefix))
+        .await?;
+    let mut keys = page.keys;
+    keys.sort();
+    assert_eq!(vec![Id(4), Id(5)], keys);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flat_store_is_paginated() -> Result<()> {
+    const CHUNK_COUNT: u64 = 20;
+    const PAGE_SIZE: usize = 4;
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], 0, UsedSpace::new(u64::MAX)).await?;
+    for index in 0..CHUNK_COUNT {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: vec![],
+            })
+            .await?;
+    }
+
+    let mut listed = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = chunk_store.keys_page(cursor, PAGE_SIZE, None).await?;
+        listed.extend(page.keys);
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    listed.sort();
+    assert_eq!((0..CHUNK_COUNT).map(Id).collect::<Vec<_>>(), listed);
+
+    // The listing the pages are taken from is refreshed once the store changes.
+    let page = chunk_store.keys_page(None, PAGE_SIZE, None).await?;
+    for index in 0..CHUNK_COUNT {
+        if !page.keys.contains(&Id(index)) {
+            chunk_store.delete(&Id(index)).await?;
+        }
+    }
+    let next = chunk_store.keys_page(page.next, PAGE_SIZE, None).await?;
+    assert!(next.keys.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn stats_are_reported() -> Result<()> {
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    let stats = chunk_store.stats().await?;
+    assert_eq!(0, stats.chunk_count);
+    assert_eq!(None, stats.last_write);
+
+    let value_sizes = [10, 2_000, 2_000, 100_000, 2_000_000];
+    for (index, value_size) in value_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: vec![0; *value_size],
+            })
+            .await?;
+    }
+    let _ = chunk_store.get(&Id(0)).await?;
+    let _ = chunk_store.get(&Id(0)).await?;
+    assert!(chunk_store.get(&Id(100)).await.is_err());
+    chunk_store.delete(&Id(1)).await?;
+
+    let stats = chunk_store.stats().await?;
+    assert_eq!(4, stats.chunk_count);
+    assert_eq!(chunk_store.total_used_space().await, stats.total_bytes);
+    let counts: Vec<_> = stats
+        .size_histogram
+        .iter()
+        .map(|bucket| bucket.count)
+        .collect();
+    assert_eq!(vec![1, 1, 0, 0, 1, 0, 1], counts);
+    let largest: Vec<_> = stats.largest.iter().map(|(name, _)| *name).collect();
+    assert_eq!(
+        vec![Id(4).name(), Id(3).name(), Id(2).name(), Id(0).name()],
+        largest
+    );
+    assert_eq!(
+        (value_sizes.len() as u64, 2, 1),
+        (stats.writes, stats.reads, stats.deletes)
+    );
+    assert!(stats.last_write.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lookups_are_served_from_the_index() -> Result<()> {
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    for index in 0..3 {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: vec![0; 32],
+            })
+            .await?;
+    }
+    chunk_store.delete(&Id(2)).await?;
+    assert!(chunk_store.has(&Id(0)).await);
+    assert!(chunk_store.has(&Id(1)).await);
+    assert!(!chunk_store.has(&Id(2)).await);
+
+    // The backend isn't consulted, so a chunk removed behind the store's back still shows...
+    let path = chunk_files(&chunk_store.volumes[0].dir, DIR_DEPTH)
+        .into_iter()
+        .find(|path| file_chunk_id(path) == Some(Id(1)))
+        .ok_or_else(|| Error::Logic("Chunk file not found".to_string()))?;
+    std::fs::remove_file(path)?;
+    assert!(chunk_store.has(&Id(1)).await);
+    drop(chunk_store);
+
+    // ...until the index is rebuilt when the store is next opened.
+    let chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    assert!(chunk_store.has(&Id(0)).await);
+    assert!(!chunk_store.has(&Id(1)).await);
+    assert!(!chunk_store.has(&Id(2)).await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunks_are_sharded_into_subdirs() -> Result<()> {
+    let mut rng = new_rng();
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
+
+    let data = Data {
+        id: Id(rng.gen()),
+        value: rng.sample_iter(&Standard).take(32).collect(),
+    };
+    chunk_store.put(&data).await?;
+
+    let store_dir = root.path().join(super::CHUNK_STORE_DIR).join("test");
+    let files = chunk_files(&store_dir, DIR_DEPTH);
+    assert_eq!(1, files.len());
+    assert!(chunk_files(&store_dir, 0)
+        .iter()
+        .all(|path| file_chunk_id(path).is_none()));
+    assert_eq!(Some(data.id), file_chunk_id(&files[0]));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flat_layout_is_migrated() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], 0, UsedSpace::new(u64::MAX)).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    drop(chunk_store);
+
+    for depth in &[DIR_DEPTH, DIR_DEPTH + 1, 1] {
+        let chunk_store: ChunkStore<Data> =
+            ChunkStore::<Data>::new(&[root.path()], *depth, UsedSpace::new(u64::MAX)).await?;
+        let mut keys = chunk_store.keys().await;
+        keys.sort();
+        assert_eq!(
+            (0..chunks.data_and_sizes.len())
+                .map(|i| Id(i as u64))
+                .collect::<Vec<_>>(),
+            keys
+        );
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            assert_eq!(*data, chunk_store.get(&Id(index as u64)).await?.value);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn orphaned_temp_files_are_removed_and_space_released() -> Result<()> {
+    let mut rng = new_rng();
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+
+    let data = Data {
+        id: Id(rng.gen()),
+        value: rng.sample_iter(&Standard).take(32).collect(),
+    };
+    chunk_store.put(&data).await?;
+    let stored = chunk_store.total_used_space().await;
+
+    // Simulate a crash after space was reserved and a temp file created, but before the rename.
+    let orphan_size = 100;
+    let key = utils::serialise(&Id(rng.gen()))?;
+    let temp_path = chunk_store.volumes[0].backend.temp_path(&key);
+    std::fs::create_dir_all(temp_path.parent().unwrap_or(root.path()))?;
+    std::fs::write(&temp_path, &[0; 10])?;
+    chunk_store
+        .used_space
+        .increase(chunk_store.volumes[0].id, orphan_size)
+        .await?;
+    drop(chunk_store);
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let chunk_store: ChunkStore<Data> =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
+    assert!(!temp_path.exists());
+    assert_eq!(stored, used_space.local(chunk_store.volumes[0].id).await);
+    assert_eq!(data, chunk_store.get(&data.id).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn corrupt_blob_is_detected_and_quarantined() -> Result<()> {
+    let rng = new_rng();
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Blob>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+
+    let intact = Blob::Public(PublicBlob::new(
+        rng.sample_iter(&Standard).take(32).collect(),
+    ));
+    let corrupt = Blob::Public(PublicBlob::new(
+        rng.sample_iter(&Standard).take(32).collect(),
+    ));
+    chunk_store.put(&intact).await?;
+    chunk_store.put(&corrupt).await?;
+    let used_space_before = chunk_store.total_used_space().await;
+
+    // Flip a bit of the blob's value on disk.
+    let file_path = chunk_store.volumes[0]
+        .backend
+        .file_path(&utils::serialise(corrupt.address())?);
+    let mut contents = std::fs::read(&file_path)?;
+    let last = contents.len() - 1;
+    contents[last] ^= 1;
+    std::fs::write(&file_path, &contents)?;
+
+    assert!(chunk_store.verify(intact.address()).await?);
+    assert!(!chunk_store.verify(corrupt.address()).await?);
+
+    chunk_store.quarantine(corrupt.address()).await?;
+    assert!(!chunk_store.has(corrupt.address()).await);
+    assert_eq!(vec![*intact.address()], chunk_store.keys().await);
+    assert_eq!(
+        used_space_before - contents.len() as u64,
+        chunk_store.total_used_space().await
+    );
+    assert!(matches!(
+        chunk_store.verify(corrupt.address()).await,
+        Err(Error::NoSuchChunk)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn used_space_is_reconciled_on_open() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+    let mut chunk_store: ChunkStore<Data> =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+
+    // Make the record stale, then unparsable.
+    let _ = chunk_store
+        .used_space
+        .reconcile(chunk_store.volumes[0].id, 1)
+        .await?;
+    let record_path = chunk_store.volumes[0].dir.join("used_space");
+    drop(chunk_store);
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let (chunk_store, reports) =
+        ChunkStore::<Data>::open(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
+    let report = &reports[0];
+    assert_eq!(1, report.recorded);
+    assert_eq!(chunks.total_size, report.actual);
+    assert_eq!(chunks.data_and_sizes.len() as u64, report.chunk_count);
+    assert_eq!(
+        chunks.total_size,
+        used_space.local(chunk_store.volumes[0].id).await
+    );
+    assert_eq!(chunks.total_size, used_space.total().await);
+    drop(chunk_store);
+
+    std::fs::write(&record_path, &[0xff])?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let (_, reports) =
+        ChunkStore::<Data>::open(&[root.path()], DIR_DEPTH, used_space.clone()).await?;
+    assert_eq!(chunks.total_size, reports[0].actual);
+    assert_eq!(chunks.total_size, used_space.total().await);
+
+    Ok(())
+}
+
+async fn exercise_backend<B: Backend>(root: &Path, options: B::Options) -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::<Data, B>::new(&[root], options, used_space.clone()).await?;
+
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        let id = Id(index as u64);
+        assert!(!chunk_store.has(&id).await);
+        chunk_store
+            .put(&Data {
+                id,
+                value: data.clone(),
+            })
+            .await?;
+        assert!(chunk_store.has(&id).await);
+        assert_eq!(*data, chunk_store.get(&id).await?.value);
+    }
+    assert_eq!(chunks.total_size, used_space.total().await);
+
+    let mut keys = chunk_store.keys().await;
+    keys.sort();
+    assert_eq!(
+        (0..chunks.data_and_sizes.len())
+            .map(|i| Id(i as u64))
+            .collect::<Vec<_>>(),
+        keys
+    );
+
+    for index in 0..chunks.data_and_sizes.len() {
+        let id = Id(index as u64);
+        chunk_store.delete(&id).await?;
+        assert!(!chunk_store.has(&id).await);
+        assert!(matches!(
+            chunk_store.get(&id).await,
+            Err(Error::NoSuchChunk)
+        ));
+    }
+    assert!(chunk_store.keys().await.is_empty());
+    assert_eq!(0, used_space.total().await);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn all_backends_store_chunks() -> Result<()> {
+    let root = temp_dir()?;
+    exercise_backend::<MemoryBackend>(&root.path().join("memory"), ()).await?;
+    // Nothing at all is written to disk.
+    assert!(!root.path().join("memory").exists());
+
+    exercise_backend::<KvBackend>(&root.path().join("kv"), ()).await?;
+    exercise_backend::<FsBackend>(&root.path().join("fs"), DIR_DEPTH).await?;
+
+    for kind in &[ChunkBackend::Fs, ChunkBackend::Kv, ChunkBackend::Memory] {
+        let options = BackendOptions {
+            kind: *kind,
+            dir_depth: DIR_DEPTH,
+        };
+        let dir = root.path().join("configured").join(kind.to_string());
+        exercise_backend::<ConfiguredBackend>(&dir, options).await?;
+    }
+    Ok(())
+}
+
+async fn open_configured(
+    root: &Path,
+    kind: ChunkBackend,
+) -> ChunkStoreResult<ChunkStore<Data, ConfiguredBackend>> {
+    let options = BackendOptions {
+        kind,
+        dir_depth: DIR_DEPTH,
+    };
+    ChunkStore::new(&[root], options, UsedSpace::new(u64::MAX)).await
+}
+
+#[tokio::test]
+async fn store_is_not_opened_with_another_backend() -> Result<()> {
+    let root = temp_dir()?;
+    let _ = open_configured(root.path(), ChunkBackend::Kv).await?;
+    assert!(matches!(
+        open_configured(root.path(), ChunkBackend::Fs).await,
+        Err(Error::ChunkBackendMismatch {
+            found: ChunkBackend::Kv,
+            configured: ChunkBackend::Fs,
+            ..
+        })
+    ));
+    let _ = open_configured(root.path(), ChunkBackend::Kv).await?;
+
+    // A store from before the backend was recorded was written by the `FsBackend`.
+    let legacy = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[legacy.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    let data = Data {
+        id: Id(0),
+        value: vec![1; 16],
+    };
+    chunk_store.put(&data).await?;
+    drop(chunk_store);
+    assert!(matches!(
+        open_configured(legacy.path(), ChunkBackend::Kv).await,
+        Err(Error::ChunkBackendMismatch { .. })
+    ));
+    let chunk_store = open_configured(legacy.path(), ChunkBackend::Fs).await?;
+    assert_eq!(data, chunk_store.get(&data.id).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_reads() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+
+    // More reads than `MAX_CONCURRENT_IO` are in flight at once.
+    let ids: Vec<_> = (0..chunks.data_and_sizes.len())
+        .map(|index| Id(index as u64))
+        .collect();
+    let retrieved = futures::future::try_join_all(ids.iter().map(|id| chunk_store.get(id))).await?;
+    for ((data, _), chunk) in chunks.data_and_sizes.iter().zip(retrieved) {
+        assert_eq!(*data, chunk.value);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cached_chunks_are_invalidated_on_put_and_delete() -> Result<()> {
+    let root = temp_dir()?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX))
+            .await?
+            .with_cache(1024);
+
+    let first = Data {
+        id: Id(0),
+        value: vec![1; 32],
+    };
+    chunk_store.put(&first).await?;
+    assert_eq!(first, chunk_store.get(&first.id).await?);
+    assert_eq!(first, chunk_store.get(&first.id).await?);
+
+    let second = Data {
+        id: Id(0),
+        value: vec![2; 32],
+    };
+    chunk_store.put(&second).await?;
+    assert_eq!(second, chunk_store.get(&second.id).await?);
+
+    chunk_store.delete(&second.id).await?;
+    assert!(matches!(
+        chunk_store.get(&second.id).await,
+        Err(Error::NoSuchChunk)
+    ));
+
+    let stats = chunk_store
+        .cache_stats()
+        .await
+        .ok_or_else(|| Error::Logic("Cache not enabled".to_string()))?;
+    assert_eq!(1, stats.hits);
+    assert_eq!(3, stats.misses);
+    assert_eq!(0, stats.size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunks_are_spread_over_roots() -> Result<()> {
+    const CHUNK_COUNT: u64 = 20;
+    let rng = new_rng();
+    let first_root = temp_dir()?;
+    let second_root = temp_dir()?;
+    let roots = [first_root.path(), second_root.path()];
+
+    let used_space = UsedSpace::new(u64::MAX);
+    for root in &roots {
+        used_space.add_volume(root, 10_000).await;
+    }
+    let mut chunk_store = ChunkStore::<Data>::new(&roots, DIR_DEPTH, used_space.clone()).await?;
+    for index in 0..CHUNK_COUNT {
+        chunk_store
+            .put(&Data {
+                id: Id(index),
+                value: rng.sample_iter(&Standard).take(32).collect(),
+            })
+            .await?;
+    }
+
+    // Each chunk is stored once, in whichever root had the most space left.
+    let first_ids: Vec<_> = chunk_files(&chunk_store.volumes[0].dir, DIR_DEPTH)
+        .iter()
+        .filter_map(|path| file_chunk_id(path))
+        .collect();
+    let second_count = chunk_files(&chunk_store.volumes[1].dir, DIR_DEPTH).len();
+    assert_eq!(CHUNK_COUNT as usize / 2, first_ids.len());
+    assert_eq!(CHUNK_COUNT as usize / 2, second_count);
+    for index in 0..CHUNK_COUNT {
+        assert!(chunk_store.get(&Id(index)).await.is_ok());
+    }
+    drop(chunk_store);
+
+    // Losing a root only loses the chunks on it.
+    std::fs::remove_dir_all(second_root.path())?;
+    let chunk_store = ChunkStore::<Data>::new(&roots, DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    for index in 0..CHUNK_COUNT {
+        let id = Id(index);
+        assert_eq!(first_ids.contains(&id), chunk_store.has(&id).await);
+    }
+    assert_eq!(first_ids.len(), chunk_store.keys().await.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chunks_are_encrypted_at_rest() -> Result<()> {
+    let rng = new_rng();
+    let root = temp_dir()?;
+    let plain = Data {
+        id: Id(0),
+        value: rng.sample_iter(&Standard).take(32).collect(),
+    };
+    let mut chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    chunk_store.put(&plain).await?;
+    drop(chunk_store);
+
+    // Chunks stored before encryption was enabled are migrated.
+    let key = EncryptionKey::random();
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, used_space.clone())
+        .await?
+        .with_encryption(Some(key.clone()))
+        .await?;
+    let sealed = Data {
+        id: Id(1),
+        value: rng.sample_iter(&Standard).take(32).collect(),
+    };
+    chunk_store.put(&sealed).await?;
+    let mut stored_size = 0;
+    for data in &[&plain, &sealed] {
+        assert_eq!(**data, chunk_store.get(&data.id).await?);
+        assert!(chunk_store.verify(&data.id).await?);
+        let contents = std::fs::read(
+            chunk_store.volumes[0]
+                .backend
+                .file_path(&utils::serialise(&data.id)?),
+        )?;
+        assert_ne!(utils::serialise(*data)?.to_vec(), contents);
+        stored_size += contents.len() as u64;
+    }
+    assert_eq!(stored_size, used_space.total().await);
+    drop(chunk_store);
+
+    // An encrypted store can't be opened without the key, nor read with another one.
+    let chunk_store =
+        ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX)).await?;
+    assert!(matches!(
+        chunk_store.with_encryption(None).await,
+        Err(Error::Encryption)
+    ));
+    let chunk_store = ChunkStore::<Data>::new(&[root.path()], DIR_DEPTH, UsedSpace::new(u64::MAX))
+        .await?
+        .with_encryption(Some(EncryptionKey::random()))
+        .await?;
+    assert!(!chunk_store.verify(&plain.id).await?);
+
+    Ok(())
+}
@@ -7,10 +7,17 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{Error, Result};
-use std::{path::Path, sync::Arc};
+use log::warn;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::sync::Mutex;
 
-const USED_SPACE_FILENAME: &str = "used_space";
+/// Name of the file recording the used space of a store, within its dir.
+pub(crate) const USED_SPACE_FILENAME: &str = "used_space";
 
 /// This holds a record (in-memory and on-disk) of the space used by a single `ChunkStore`, and also
 /// an in-memory record of the total space used by all `ChunkStore`s.
@@ -49,6 +56,11 @@ impl UsedSpace {
         inner::UsedSpace::max_capacity(self.inner.clone()).await
     }
 
+    /// Changes the maximum capacity, e.g. as it has been reconfigured
+    pub async fn set_max_capacity(&self, max_capacity: u64) {
+        inner::UsedSpace::set_max_capacity(self.inner.clone(), max_capacity).await
+    }
+
     /// Returns the total used space as a snapshot
     /// Note, due to the async nature of this, the value
     /// may be stale by the time it is read if there are multiple
@@ -61,17 +73,48 @@ impl UsedSpace {
     /// Note, due to the async nature of this, the value
     /// may be stale by the time it is read if there are multiple
     /// writers
-    #[allow(unused)]
     pub async fn local(&self, id: StoreId) -> u64 {
         inner::UsedSpace::local(self.inner.clone(), id).await
     }
 
+    /// Returns the space a local store can still consume, going by
+    /// the maximum capacity, its quota and the capacity of its volume
+    pub async fn available(&self, id: StoreId) -> u64 {
+        inner::UsedSpace::available(self.inner.clone(), id).await
+    }
+
+    /// Returns the ratio of used to maximum space that is the highest of
+    /// those of the limits applying to a local store
+    pub async fn used_ratio(&self, id: StoreId) -> f64 {
+        inner::UsedSpace::used_ratio(self.inner.clone(), id).await
+    }
+
+    /// Limit the space that the local stores of the same kind as the given one
+    /// (i.e. in directories of the same name, across volumes) can use together,
+    /// on top of the maximum capacity shared by all stores
+    pub async fn set_quota(&self, id: StoreId, quota: Option<u64>) -> Result<()> {
+        inner::UsedSpace::set_quota(self.inner.clone(), id, quota).await
+    }
+
+    /// Limit the space that the local stores within `root` (e.g. a
+    /// mounted disk) can use together to `capacity`
+    pub async fn add_volume<T: AsRef<Path>>(&self, root: T, capacity: u64) {
+        inner::UsedSpace::add_volume(self.inner.clone(), root, capacity).await
+    }
+
     /// Add an object and file store to track used space of a single
-    /// `ChunkStore`
+    /// `ChunkStore`. A store previously added for the same `dir` is
+    /// replaced, so that reopening a store doesn't count it twice
     pub async fn add_local_store<T: AsRef<Path>>(&self, dir: T) -> Result<StoreId> {
         inner::UsedSpace::add_local_store(self.inner.clone(), dir).await
     }
 
+    /// Add a store to track used space of a single `ChunkStore`
+    /// which doesn't persist its contents, and so has no on-disk record
+    pub async fn add_in_memory_store(&self) -> StoreId {
+        inner::UsedSpace::add_in_memory_store(self.inner.clone()).await
+    }
+
     /// Increase the used amount of a single chunk store and the global used value
     pub async fn increase(&self, id: StoreId, consumed: u64) -> Result<()> {
         inner::UsedSpace::increase(self.inner.clone(), id, consumed).await
@@ -81,16 +124,102 @@ impl UsedSpace {
     pub async fn decrease(&self, id: StoreId, released: u64) -> Result<()> {
         inner::UsedSpace::decrease(self.inner.clone(), id, released).await
     }
+
+    /// Write any buffered changes to the on-disk records of the local stores.
+    /// The node does so periodically and on shutdown; changes are otherwise
+    /// written when the last instance of this is dropped.
+    pub async fn flush(&self) -> Result<()> {
+        inner::UsedSpace::flush(self.inner.clone()).await
+    }
+
+    /// Returns a snapshot of the space used, overall, by each kind of store
+    /// and on each volume
+    pub async fn stats(&self) -> UsedSpaceStats {
+        inner::UsedSpace::stats(self.inner.clone()).await
+    }
+
+    /// Overwrite the used amount of a single chunk store with the space it actually
+    /// consumes, adjusting the global used value accordingly.
+    /// Returns the previously recorded amount.
+    pub async fn reconcile(&self, id: StoreId, actual: u64) -> Result<u64> {
+        inner::UsedSpace::reconcile(self.inner.clone(), id, actual).await
+    }
+}
+
+/// A snapshot of the space used by the `ChunkStore`s sharing a `UsedSpace`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UsedSpaceStats {
+    /// The space all stores can use together.
+    pub max_capacity: u64,
+    /// The space used by all stores.
+    pub total: u64,
+    /// The space used by the stores of each kind (i.e. data type), by the name of their dirs.
+    pub kinds: BTreeMap<String, KindSpaceStats>,
+    /// The space used on each volume with a capacity of its own, by its root dir.
+    pub volumes: BTreeMap<PathBuf, VolumeSpaceStats>,
+}
+
+/// The space used by the stores of one kind, across volumes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KindSpaceStats {
+    /// The space used.
+    pub used: u64,
+    /// The space the stores of the kind can use together, if limited.
+    pub quota: Option<u64>,
+}
+
+/// The space used on one volume.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VolumeSpaceStats {
+    /// The space used.
+    pub used: u64,
+    /// The space the stores on the volume can use together.
+    pub capacity: u64,
+}
+
+/// The outcome of reconciling the recorded used space of a `ChunkStore` with the chunk files
+/// actually on disk.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UsedSpaceReport {
+    /// Directory of the `ChunkStore`.
+    pub dir: PathBuf,
+    /// Number of chunk files found.
+    pub chunk_count: u64,
+    /// Used space according to the record, before it was corrected.
+    pub recorded: u64,
+    /// Sum of the sizes of the chunk files.
+    pub actual: u64,
+}
+
+impl UsedSpaceReport {
+    /// Whether the record matched the actual contents.
+    pub fn is_consistent(&self) -> bool {
+        self.recorded == self.actual
+    }
+}
+
+impl Display for UsedSpaceReport {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}: {} chunks, {} bytes on disk, {} bytes recorded",
+            self.dir.display(),
+            self.chunk_count,
+            self.actual,
+            self.recorded
+        )?;
+        if !self.is_consistent() {
+            write!(formatter, " (corrected)")?;
+        }
+        Ok(())
+    }
 }
 
 mod inner {
 
     use super::*;
-    use std::{collections::HashMap, io::SeekFrom};
-    use tokio::{
-        fs::{File, OpenOptions},
-        io::{AsyncReadExt, AsyncWriteExt},
-    };
+    use crate::chunk_store::journal::Journal;
+    use std::collections::HashMap;
 
     /// Tracks the Used Space of all `ChunkStore` objects
     /// registered with it, as well as the combined amount
@@ -104,18 +233,50 @@ mod inner {
         local_stores: HashMap<StoreId, LocalUsedSpace>,
         /// next local `ChunkStore` id to use
         next_id: StoreId,
+        /// the root directories of volumes with a capacity of their own
+        volumes: Vec<(PathBuf, u64)>,
     }
 
     /// An entry used to track the used space of a single `ChunkStore`
     #[derive(Debug)]
     struct LocalUsedSpace {
+        // Directory of the on-disk record, if the store is persisted.
+        pub dir: Option<PathBuf>,
+        // The maximum value (inclusive) that the `local_value`s of all stores of
+        // the same kind can attain together, if limited.
+        pub quota: Option<u64>,
         // Space consumed by this one `ChunkStore`.
         pub local_value: u64,
-        // File used to maintain on-disk record of `local_value`.
-        // TODO: maybe a good idea to maintain a journal that is only flushed occasionally
-        // to ensure stale entries aren't recorded, and to avoid holding the lock for the
-        // whole inner::UsedSpace struct during the entirety of the file write.
-        pub local_record: File,
+        // The on-disk record of `local_value`, if the store is persisted.
+        // Changes are only flushed to disk occasionally, so that they don't hold
+        // the lock for the whole inner::UsedSpace struct during a file sync each.
+        pub journal: Option<Journal>,
+    }
+
+    impl LocalUsedSpace {
+        async fn increase(&mut self, consumed: u64) -> Result<()> {
+            if let Some(journal) = &mut self.journal {
+                journal.increase(consumed).await?;
+            }
+            self.local_value = self.local_value.saturating_add(consumed);
+            Ok(())
+        }
+
+        async fn decrease(&mut self, released: u64) -> Result<()> {
+            if let Some(journal) = &mut self.journal {
+                journal.decrease(released).await?;
+            }
+            self.local_value = self.local_value.saturating_sub(released);
+            Ok(())
+        }
+
+        async fn reset(&mut self, value: u64) -> Result<()> {
+            if let Some(journal) = &mut self.journal {
+                journal.reset(value).await?;
+            }
+            self.local_value = value;
+            Ok(())
+        }
     }
 
     impl UsedSpace {
@@ -125,7 +286,113 @@ mod inner {
                 total_value: 0u64,
                 local_stores: HashMap::new(),
                 next_id: 0u64,
+                volumes: Vec::new(),
+            }
+        }
+
+        /// The (used, capacity) pairs of each of the limits applying to a
+        /// local store: the maximum capacity, its quota, and its volumes
+        fn limits(&self, id: StoreId) -> Result<Vec<(u64, u64)>> {
+            let local_store = self.local_stores.get(&id).ok_or(Error::NoStoreId)?;
+            let mut limits = vec![(self.total_value, self.max_capacity)];
+            let used_by = |is_counted: &dyn Fn(&Path) -> bool| {
+                self.local_stores
+                    .iter()
+                    .filter(|(other_id, other)| {
+                        **other_id == id
+                            || matches!(other.dir.as_deref(), Some(dir) if is_counted(dir))
+                    })
+                    .map(|(_, other)| other.local_value)
+                    .sum::<u64>()
+            };
+            if let Some(quota) = local_store.quota {
+                let kind = local_store.dir.as_deref().and_then(Path::file_name);
+                let used = used_by(&|dir| kind.is_some() && dir.file_name() == kind);
+                limits.push((used, quota));
+            }
+            if let Some(dir) = &local_store.dir {
+                for (root, capacity) in &self.volumes {
+                    if dir.starts_with(root) {
+                        let used = used_by(&|dir| dir.starts_with(root));
+                        limits.push((used, *capacity));
+                    }
+                }
+            }
+            Ok(limits)
+        }
+
+        /// Returns the space a local store can still consume
+        pub async fn available(used_space: Arc<Mutex<UsedSpace>>, id: StoreId) -> u64 {
+            let used_space_lock = used_space.lock().await;
+            used_space_lock
+                .limits(id)
+                .unwrap_or_default()
+                .iter()
+                .map(|(used, capacity)| capacity.saturating_sub(*used))
+                .min()
+                .unwrap_or(0)
+        }
+
+        /// Returns the highest ratio of used to maximum space of the limits
+        /// applying to a local store
+        pub async fn used_ratio(used_space: Arc<Mutex<UsedSpace>>, id: StoreId) -> f64 {
+            let used_space_lock = used_space.lock().await;
+            used_space_lock
+                .limits(id)
+                .unwrap_or_default()
+                .iter()
+                .map(|(used, capacity)| *used as f64 / *capacity as f64)
+                .fold(0.0, f64::max)
+        }
+
+        pub async fn stats(used_space: Arc<Mutex<UsedSpace>>) -> UsedSpaceStats {
+            let used_space_lock = used_space.lock().await;
+            let mut stats = UsedSpaceStats {
+                max_capacity: used_space_lock.max_capacity,
+                total: used_space_lock.total_value,
+                ..Default::default()
+            };
+            for (root, capacity) in &used_space_lock.volumes {
+                let _ = stats.volumes.insert(
+                    root.clone(),
+                    VolumeSpaceStats {
+                        used: 0,
+                        capacity: *capacity,
+                    },
+                );
+            }
+            for local_store in used_space_lock.local_stores.values() {
+                let dir = match &local_store.dir {
+                    Some(dir) => dir,
+                    None => continue,
+                };
+                if let Some(kind) = dir.file_name() {
+                    let kind_stats = stats
+                        .kinds
+                        .entry(kind.to_string_lossy().into_owned())
+                        .or_default();
+                    kind_stats.used += local_store.local_value;
+                    kind_stats.quota = kind_stats.quota.or(local_store.quota);
+                }
+                for (root, volume_stats) in stats.volumes.iter_mut() {
+                    if dir.starts_with(root) {
+                        volume_stats.used += local_store.local_value;
+                    }
+                }
             }
+            stats
+        }
+
+        /// Adds a volume with a capacity of its own
+        pub async fn add_volume<T: AsRef<Path>>(
+            used_space: Arc<Mutex<UsedSpace>>,
+            root: T,
+            capacity: u64,
+        ) {
+            let mut used_space_lock = used_space.lock().await;
+            used_space_lock
+                .volumes
+                .push((root.as_ref().to_path_buf(), capacity));
         }
 
         /// Returns the maximum capacity (e.g. the maximum
@@ -135,6 +402,12 @@ mod inner {
             used_space_lock.max_capacity
         }
 
+        /// Changes the maximum capacity
+        pub async fn set_max_capacity(used_space: Arc<Mutex<UsedSpace>>, max_capacity: u64) {
+            let mut used_space_lock = used_space.lock().await;
+            used_space_lock.max_capacity = max_capacity;
+        }
+
         /// Returns the total used space as a snapshot
         /// Note, due to the async nature of this, the value
         /// may be stale by the time it is read if there are multiple
@@ -156,6 +429,21 @@ mod inner {
                 .map_or(0, |res| res.local_value)
         }
 
+        /// Sets or clears the quota of a local store
+        pub async fn set_quota(
+            used_space: Arc<Mutex<UsedSpace>>,
+            id: StoreId,
+            quota: Option<u64>,
+        ) -> Result<()> {
+            let mut used_space_lock = used_space.lock().await;
+            used_space_lock
+                .local_stores
+                .get_mut(&id)
+                .ok_or(Error::NoStoreId)?
+                .quota = quota;
+            Ok(())
+        }
+
         /// Adds a new record for tracking the actions
         /// of a local chunk store as part of the global
         /// used amount tracking
@@ -163,36 +451,63 @@ mod inner {
             used_space: Arc<Mutex<UsedSpace>>,
             dir: T,
         ) -> Result<StoreId> {
-            let mut local_record = OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(dir.as_ref().join(USED_SPACE_FILENAME))
-                .await?;
-
-            // try read
-            let mut buffer = vec![];
-            let could_read = local_record.read_to_end(&mut buffer).await.is_ok();
-            let has_value = !buffer.is_empty();
-            let local_value = if could_read && has_value {
-                // TODO - if this can't be parsed, we should consider emptying `dir` of any chunks.
-                bincode::deserialize::<u64>(&buffer)?
-            } else {
-                let mut bytes = Vec::<u8>::new();
-                bincode::serialize_into(&mut bytes, &0_u64)?;
-                local_record.write_all(&bytes).await?;
-                0
+            // Drop any previous entry first, so that its buffered changes are
+            // written to the journal before it is replayed.
+            Self::remove_local_store(used_space.clone(), dir.as_ref()).await;
+            let (mut journal, replayed) =
+                Journal::open(&dir.as_ref().join(USED_SPACE_FILENAME)).await?;
+            if replayed.is_none() {
+                // The record is rebuilt when the store's contents are reconciled.
+                warn!(
+                    "Could not parse used space record in {}",
+                    dir.as_ref().display()
+                );
+                journal.reset(0).await?;
+            }
+            let local_store = LocalUsedSpace {
+                dir: Some(dir.as_ref().to_path_buf()),
+                quota: None,
+                local_value: journal.value(),
+                journal: Some(journal),
             };
+            Ok(Self::insert(used_space, local_store).await)
+        }
 
+        /// Adds a new record, without an on-disk counterpart,
+        /// for tracking the actions of a local chunk store
+        pub async fn add_in_memory_store(used_space: Arc<Mutex<UsedSpace>>) -> StoreId {
             let local_store = LocalUsedSpace {
-                local_value,
-                local_record,
+                dir: None,
+                quota: None,
+                local_value: 0,
+                journal: None,
             };
+            Self::insert(used_space, local_store).await
+        }
+
+        async fn remove_local_store(used_space: Arc<Mutex<UsedSpace>>, dir: &Path) {
+            let mut used_space_lock = used_space.lock().await;
+            let id = used_space_lock
+                .local_stores
+                .iter()
+                .find(|(_, local_store)| local_store.dir.as_deref() == Some(dir))
+                .map(|(id, _)| *id);
+            if let Some(local_store) = id.and_then(|id| used_space_lock.local_stores.remove(&id)) {
+                used_space_lock.total_value = used_space_lock
+                    .total_value
+                    .saturating_sub(local_store.local_value);
+            }
+        }
+
+        async fn insert(used_space: Arc<Mutex<UsedSpace>>, local_store: LocalUsedSpace) -> StoreId {
             let mut used_space_lock = used_space.lock().await;
             let id = used_space_lock.next_id;
             used_space_lock.next_id += 1;
+            used_space_lock.total_value = used_space_lock
+                .total_value
+                .saturating_add(local_store.local_value);
             let _ = used_space_lock.local_stores.insert(id, local_store);
-            Ok(id)
+            id
         }
 
         /// Asynchronous implementation to increase used space in a local store
@@ -203,35 +518,23 @@ mod inner {
             consumed: u64,
         ) -> Result<()> {
             let mut used_space_lock = used_space.lock().await;
-            let new_total = used_space_lock
-                .total_value
-                .checked_add(consumed)
-                .ok_or(Error::NotEnoughSpace)?;
-            if new_total > used_space_lock.max_capacity {
-                return Err(Error::NotEnoughSpace);
+            for (used, capacity) in used_space_lock.limits(id)? {
+                let new_used = used.checked_add(consumed).ok_or(Error::NotEnoughSpace)?;
+                if new_used > capacity {
+                    return Err(Error::NotEnoughSpace);
+                }
             }
-            let new_local = used_space_lock
+            let new_total = used_space_lock.total_value + consumed;
+            let local_store = used_space_lock
                 .local_stores
-                .get(&id)
-                .ok_or(Error::NoStoreId)?
+                .get_mut(&id)
+                .ok_or(Error::NoStoreId)?;
+            let _ = local_store
                 .local_value
                 .checked_add(consumed)
                 .ok_or(Error::NotEnoughSpace)?;
-
-            {
-                let record = &mut used_space_lock
-                    .local_stores
-                    .get_mut(&id)
-                    .ok_or(Error::NoStoreId)?
-                    .local_record;
-                Self::write_local_to_file(record, new_local).await?;
-            }
+            local_store.increase(consumed).await?;
             used_space_lock.total_value = new_total;
-            used_space_lock
-                .local_stores
-                .get_mut(&id)
-                .ok_or(Error::NoStoreId)?
-                .local_value = new_local;
 
             Ok(())
         }
@@ -244,42 +547,46 @@ mod inner {
             released: u64,
         ) -> Result<()> {
             let mut used_space_lock = used_space.lock().await;
-            let new_local = used_space_lock
-                .local_stores
-                .get_mut(&id)
-                .ok_or(Error::NoStoreId)?
-                .local_value
-                .saturating_sub(released);
             let new_total = used_space_lock.total_value.saturating_sub(released);
-            {
-                let record = &mut used_space_lock
-                    .local_stores
-                    .get_mut(&id)
-                    .ok_or(Error::NoStoreId)?
-                    .local_record;
-                Self::write_local_to_file(record, new_local).await?;
-            }
-            used_space_lock.total_value = new_total;
             used_space_lock
                 .local_stores
                 .get_mut(&id)
                 .ok_or(Error::NoStoreId)?
-                .local_value = new_local;
+                .decrease(released)
+                .await?;
+            used_space_lock.total_value = new_total;
             Ok(())
         }
 
-        /// helper to write the contents of local to file
-        /// NOTE: For now, ou should hold the lock on the inner while doing this
-        /// It's slow, but maintains behaviour from the previous implementation
-        async fn write_local_to_file(record: &mut File, local: u64) -> Result<()> {
-            record.set_len(0).await?;
-            let _ = record.seek(SeekFrom::Start(0)).await?;
-
-            let mut contents = Vec::<u8>::new();
-            bincode::serialize_into(&mut contents, &local)?;
-            record.write_all(&contents).await?;
-            record.sync_all().await?;
+        /// Asynchronous implementation to set the used space of a local store
+        /// to its actual value, adjusting the global value by the difference
+        pub async fn reconcile(
+            used_space: Arc<Mutex<UsedSpace>>,
+            id: StoreId,
+            actual: u64,
+        ) -> Result<u64> {
+            let mut used_space_lock = used_space.lock().await;
+            let local_store = used_space_lock
+                .local_stores
+                .get_mut(&id)
+                .ok_or(Error::NoStoreId)?;
+            let recorded = local_store.local_value;
+            local_store.reset(actual).await?;
+            used_space_lock.total_value = used_space_lock
+                .total_value
+                .saturating_sub(recorded)
+                .saturating_add(actual);
+            Ok(recorded)
+        }
 
+        /// Writes the changes buffered for all local stores to disk
+        pub async fn flush(used_space: Arc<Mutex<UsedSpace>>) -> Result<()> {
+            let mut used_space_lock = used_space.lock().await;
+            for local_store in used_space_lock.local_stores.values_mut() {
+                if let Some(journal) = &mut local_store.journal {
+                    journal.flush().await?;
+                }
+            }
             Ok(())
         }
     }
@@ -317,9 +624,9 @@ mod tests {
         let id = used_space.add_local_store(&store_dir).await?;
         // get a random vec of u64 by adding u32 (avoid overflow)
         let mut rng = rand::thread_rng();
-        let bytes = crate::utils::random_vec(&mut rng, std::mem::size_of::<u32>() * NUMS_TO_ADD);
+        let bytes = crate::utils::random_vec(&mut rng, size_of::<u32>() * NUMS_TO_ADD);
         let mut nums = Vec::new();
-        for chunk in bytes.as_slice().chunks_exact(std::mem::size_of::<u32>()) {
+        for chunk in bytes.as_slice().chunks_exact(size_of::<u32>()) {
             let mut num = 0u32;
             for (i, component) in chunk.iter().enumerate() {
                 num |= (*component as u32) << (i * 8);
@@ -350,4 +657,81 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn used_space_journal_is_replayed() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        used_space.increase(id, 100).await?;
+        used_space.decrease(id, 30).await?;
+        used_space.flush().await?;
+        // Not flushed explicitly, but when dropped.
+        used_space.increase(id, 5).await?;
+        drop(used_space);
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(75, used_space.local(id).await);
+        assert_eq!(75, used_space.total().await);
+        drop(used_space);
+
+        // A torn entry, from an interrupted flush, is dropped.
+        let record_path = store_dir.path().join(super::USED_SPACE_FILENAME);
+        let mut contents = std::fs::read(&record_path)?;
+        contents.extend_from_slice(&[1, 0, 0]);
+        std::fs::write(&record_path, &contents)?;
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(75, used_space.local(id).await);
+        used_space.increase(id, 25).await?;
+        drop(used_space);
+
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir).await?;
+        assert_eq!(100, used_space.local(id).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn used_space_quotas_are_enforced_per_store() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let first_dir = create_temp_store(&root_dir)?;
+        let second_dir = create_temp_store(&root_dir)?;
+
+        let used_space = UsedSpace::new(100);
+        let first = used_space.add_local_store(&first_dir).await?;
+        let second = used_space.add_local_store(&second_dir).await?;
+        used_space.set_quota(first, Some(30)).await?;
+
+        used_space.increase(first, 30).await?;
+        assert!(matches!(
+            used_space.increase(first, 1).await,
+            Err(Error::NotEnoughSpace)
+        ));
+        used_space.increase(second, 70).await?;
+        assert!(matches!(
+            used_space.increase(second, 1).await,
+            Err(Error::NotEnoughSpace)
+        ));
+        assert_eq!(100, used_space.total().await);
+
+        // Re-adding a store replaces it, rather than counting it twice.
+        let first = used_space.add_local_store(&first_dir).await?;
+        assert_eq!(30, used_space.local(first).await);
+        assert_eq!(100, used_space.total().await);
+
+        used_space.add_volume(&root_dir, 200).await;
+        let stats = used_space.stats().await;
+        assert_eq!((100, 100), (stats.max_capacity, stats.total));
+        assert_eq!(2, stats.kinds.len());
+        assert_eq!(Some(30), stats.kinds.values().map(|kind| kind.used).min());
+        assert_eq!(100, stats.volumes[root_dir.path()].used);
+
+        Ok(())
+    }
 }
@@ -8,26 +8,41 @@
 
 #![allow(trivial_numeric_casts)] // FIXME
 
-use crate::{Error, Result};
+use crate::{capacity::Pricing, chunk_store::ChunkBackend, Error, Result};
 use log::{debug, Level};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sn_routing::TransportConfig as NetworkConfig;
-use std::convert::Infallible;
-use std::net::AddrParseError;
 use std::num::ParseIntError;
 use std::{
+    collections::BTreeMap,
+    env,
+    fmt::{self, Display, Formatter},
     fs::{self, File},
-    io::{self, BufReader},
+    io,
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
-use structopt::StructOpt;
+use structopt::{clap::ArgMatches, StructOpt};
 
 const CONFIG_FILE: &str = "node.config";
+/// Prefix of the environment variables setting config keys, e.g. `SN_NODE_MAX_CAPACITY` for
+/// `max-capacity`.
+const ENV_PREFIX: &str = "SN_NODE_";
+/// Key of the config file option, which can only be set on the command line, or in the
+/// environment.
+const CONFIG_FILE_KEY: &str = "config-file";
 const CONNECTION_INFO_FILE: &str = "node_connection_info.config";
 const DEFAULT_ROOT_DIR_NAME: &str = "root_dir";
-const DEFAULT_MAX_CAPACITY: u64 = 2 * 1024 * 1024 * 1024;
-const ARGS: [&str; 19] = [
+pub(crate) const DEFAULT_MAX_CAPACITY: u64 = 2 * 1024 * 1024 * 1024;
+const DEFAULT_CHUNK_DIR_DEPTH: usize = 2;
+const DEFAULT_SCRUB_RATE: u64 = 60;
+const DEFAULT_CHUNK_CACHE_SIZE: u64 = 0;
+const DEFAULT_CLIENT_REQUEST_RATE: u64 = 100;
+const DEFAULT_CLIENT_REQUEST_BURST: u64 = 200;
+/// The config keys, as named on the command line.
+const ARGS: [&str; 33] = [
     "wallet-id",
     "max-capacity",
     "root-dir",
@@ -47,9 +62,38 @@ const ARGS: [&str; 19] = [
     "local",
     "fresh",
     "clean",
+    "chunk-dir-depth",
+    "chunk-store-backend",
+    "scrub-rate",
+    "chunk-cache-size",
+    "blob-quota",
+    "map-quota",
+    "sequence-quota",
+    "data-dir",
+    "encrypt-at-rest",
+    "pricing",
+    "client-request-rate",
+    "client-request-burst",
+    "bootstrap-cache-dir",
+    "forward-port",
+];
+/// The config keys set to "true" or "false" in config files and the environment.
+const FLAGS: [&str; 8] = [
+    "first",
+    "update",
+    "update-only",
+    "local",
+    "fresh",
+    "clean",
+    "encrypt-at-rest",
+    "forward-port",
 ];
 
 /// Node configuration
+///
+/// Values are read from, in increasing order of precedence: the config file, the `SN_NODE_*`
+/// environment variables (e.g. `SN_NODE_MAX_CAPACITY=1024`) and the command line. Unknown keys
+/// are an error in all of them.
 #[derive(Default, Clone, Debug, Serialize, Deserialize, Eq, PartialEq, StructOpt)]
 #[structopt(rename_all = "kebab-case", bin_name = "sn_node")]
 #[structopt(global_settings = &[structopt::clap::AppSettings::ColoredHelp])]
@@ -61,6 +105,18 @@ pub struct Config {
     /// Upper limit in bytes for allowed network storage on this node.
     #[structopt(short, long)]
     max_capacity: Option<u64>,
+    /// Upper limit in bytes for the part of `max-capacity` used by blob chunks. If not set, blobs
+    /// can use all of it.
+    #[structopt(long)]
+    blob_quota: Option<u64>,
+    /// Upper limit in bytes for the part of `max-capacity` used by maps. If not set, maps can use
+    /// all of it.
+    #[structopt(long)]
+    map_quota: Option<u64>,
+    /// Upper limit in bytes for the part of `max-capacity` used by sequences. If not set,
+    /// sequences can use all of it.
+    #[structopt(long)]
+    sequence_quota: Option<u64>,
     /// Root directory for ChunkStores and cached state. If not set, it defaults to "root_dir"
     /// within the sn_node project data directory, located at:
     /// Linux: $HOME/.safe/node/root_dir
@@ -68,6 +124,49 @@ pub struct Config {
     /// MacOS: $HOME/.safe/node/root_dir
     #[structopt(short, long, parse(from_os_str))]
     root_dir: Option<PathBuf>,
+    /// Directory to store chunks in, with the upper limit in bytes for the space they can use
+    /// there, as "PATH=CAPACITY". Can be given several times, e.g. once per disk, in which case
+    /// chunks are spread over them. If not set, chunks are stored in the root directory, and
+    /// `max-capacity` defaults to the sum of the capacities.
+    #[structopt(long = "data-dir", name = "data-dir", number_of_values = 1)]
+    #[serde(default)]
+    data_dirs: Vec<DataDir>,
+    /// Number of hex-prefix subdirectory levels chunk files are spread over (0 to 4). If not set,
+    /// it defaults to 2. Existing chunk stores are migrated to the configured depth on startup.
+    #[structopt(long)]
+    chunk_dir_depth: Option<usize>,
+    /// Backend the chunks are kept in: "fs" (a file per chunk), "kv" (an embedded key-value
+    /// database) or "memory" (lost on restart, e.g. for testnets). If not set, it defaults to
+    /// "fs". Existing chunk stores are not migrated, and fail to open with another backend.
+    #[structopt(long)]
+    chunk_store_backend: Option<ChunkBackend>,
+    /// Number of stored chunks per minute that an Adult re-reads and checks for corruption. If not
+    /// set, it defaults to 60. Set it to 0 to disable the check.
+    #[structopt(long)]
+    scrub_rate: Option<u64>,
+    /// Size in bytes of the in-memory cache of recently read chunks kept by an Adult. If not set,
+    /// it defaults to 0, which disables the cache.
+    #[structopt(long)]
+    chunk_cache_size: Option<u64>,
+    /// Encrypt the chunks and databases stored on disk, with a key kept in the root directory.
+    /// Existing data is encrypted on startup. Once enabled, it can't be disabled for the root
+    /// directory.
+    #[structopt(long)]
+    encrypt_at_rest: bool,
+    /// Policy an Elder prices the storage of data with: "curve" (the cost grows with the square of
+    /// the size of the data) or "linear" (linearly, plus an overhead per request). If not set, it
+    /// defaults to "curve".
+    #[structopt(long)]
+    pricing: Option<Pricing>,
+    /// Number of queries and transfers per second an Elder serves each client key, and each
+    /// client socket address. If not set, it defaults to 100. Set it to 0 to disable the limit.
+    /// Paid data writes are not limited.
+    #[structopt(long)]
+    client_request_rate: Option<u64>,
+    /// Number of queries and transfers an Elder serves each client key, and each client socket
+    /// address, in a burst above `client-request-rate`. If not set, it defaults to 200.
+    #[structopt(long)]
+    client_request_burst: Option<u64>,
     /// Verbose output. `-v` is equivalent to logging with `warn`, `-vv` to `info`, `-vvv` to
     /// `debug`, `-vvvv` to `trace`. This flag overrides RUST_LOG.
     #[structopt(short, long, parse(from_occurrences))]
@@ -93,39 +192,220 @@ pub struct Config {
     /// Attempt to self-update without starting the node process
     #[structopt(long, name = "update-only")]
     update_only: bool,
+    /// Config file to read, in TOML if its extension is ".toml", or in JSON otherwise. If not set,
+    /// it defaults to "node.config" within the sn_node project config directory, if present. Can
+    /// also be set with the SN_NODE_CONFIG_FILE environment variable.
+    #[structopt(long, parse(from_os_str))]
+    #[serde(skip)]
+    config_file: Option<PathBuf>,
+    /// Print the effective config, and where each value comes from, instead of starting the node.
+    #[structopt(long)]
+    #[serde(skip)]
+    print_config: bool,
+    #[structopt(subcommand)]
+    #[serde(skip)]
+    #[allow(missing_docs)]
+    command: Option<Command>,
+    #[structopt(skip)]
+    #[serde(skip)]
+    sources: BTreeMap<String, ConfigSource>,
 }
 
-impl Config {
-    /// Returns a new `Config` instance.  Tries to read from the default node config file location,
-    /// and overrides values with any equivalent command line args.
-    pub fn new() -> Result<Self, Error> {
-        let mut config = match Self::read_from_file() {
-            Ok(Some(config)) => config,
-            Ok(None) | Err(_) => Default::default(),
-        };
-
-        let command_line_args = Config::clap().get_matches();
+/// Where the value of a config key comes from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConfigSource {
+    /// The key is not set, so has its default value.
+    Default,
+    /// A config file.
+    File(PathBuf),
+    /// An environment variable.
+    Env(String),
+    /// The command line.
+    CommandLine,
+}
 
-        if command_line_args.occurrences_of(ARGS[17]) != 0 {
-            config = Config::default();
+impl Display for ConfigSource {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Default => write!(formatter, "default"),
+            Self::File(path) => write!(formatter, "config file {}", path.display()),
+            Self::Env(name) => write!(formatter, "environment variable {}", name),
+            Self::CommandLine => write!(formatter, "command line"),
         }
+    }
+}
+
+/// A directory to store chunks in, e.g. on a disk of its own, with the space they can use there.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DataDir {
+    /// Root directory of the `ChunkStore`s on this disk.
+    pub path: PathBuf,
+    /// Upper limit in bytes for the space used by chunks in `path`.
+    pub capacity: u64,
+}
+
+impl FromStr for DataDir {
+    type Err = Error;
 
-        if command_line_args.occurrences_of(ARGS[18]) != 0 {
+    /// Parses "PATH=CAPACITY".
+    fn from_str(value: &str) -> Result<Self> {
+        let mut parts = value.rsplitn(2, '=');
+        let capacity = parts.next().unwrap_or_default();
+        let path = parts.next().ok_or_else(|| {
+            Error::Logic(format!(
+                "Config file error: expected PATH=CAPACITY, got {:?}",
+                value
+            ))
+        })?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            capacity: capacity
+                .parse()
+                .map_err(|e: ParseIntError| Error::Logic(format!("Config file error: {:?}", e)))?,
+        })
+    }
+}
+
+impl Display for DataDir {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}={}", self.path.display(), self.capacity)
+    }
+}
+
+/// Maintenance commands, run instead of starting the node.
+#[derive(Clone, Debug, Eq, PartialEq, StructOpt)]
+pub enum Command {
+    /// Check the recorded used space of the chunk stores in the data directories against the chunk
+    /// files actually stored, and correct the records where they differ.
+    Fsck,
+    /// Write the chunk stores, databases and keys of the node to an archive, e.g. to move the node
+    /// to new hardware.
+    Export {
+        /// Path of the archive to write.
+        #[structopt(long, parse(from_os_str))]
+        to: PathBuf,
+    },
+    /// Restore the chunk stores, databases and keys of a node from an archive written by `export`,
+    /// into empty root and data directories.
+    Import {
+        /// Path of the archive to read.
+        #[structopt(long, parse(from_os_str))]
+        from: PathBuf,
+    },
+}
+
+impl Config {
+    /// Returns a new `Config` instance, read from the config file, the environment and the command
+    /// line args, in increasing order of precedence.
+    pub fn new() -> Result<Self, Error> {
+        let command_line_args = Config::clap().get_matches();
+        if command_line_args.occurrences_of("clean") != 0 {
             Self::clear_from_disk().unwrap_or_else(|_| {
                 log::error!("Error deleting config file from disk");
             })
         }
+        Self::from_sources(&command_line_args, env::vars())
+    }
+
+    /// Reads the config again, as `new` does, e.g. as the config file has changed. The config
+    /// file isn't cleared, even if `--clean` was given.
+    pub fn reload() -> Result<Self> {
+        Self::from_sources(&Config::clap().get_matches(), env::vars())
+    }
+
+    fn from_sources<I>(command_line_args: &ArgMatches, vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        let mut config = Config::default();
+
+        let config_file_var = env_var_name(CONFIG_FILE_KEY);
+        let config_file = match command_line_args.value_of_os(CONFIG_FILE_KEY) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => vars
+                .iter()
+                .find(|(name, _)| *name == config_file_var)
+                .map(|(_, path)| PathBuf::from(path)),
+        };
+        // A fresh node ignores the config file, as it is reset to the defaults.
+        let fresh = command_line_args.occurrences_of("fresh") != 0;
+        if !fresh {
+            let contents = match &config_file {
+                Some(path) => {
+                    let contents = Self::read_file(path).map_err(|error| match error {
+                        Error::Io(error) => Error::InvalidConfigFile {
+                            path: path.clone(),
+                            reason: error.to_string(),
+                        },
+                        error => error,
+                    })?;
+                    Some((path.clone(), contents))
+                }
+                None => {
+                    let path = project_dirs()?.join(CONFIG_FILE);
+                    match Self::read_file(&path) {
+                        Ok(contents) => Some((path, contents)),
+                        Err(Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+                            debug!("No config file available at {}", path.display());
+                            None
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+            };
+            if let Some((path, contents)) = contents {
+                config.set_file_values(&path, contents)?;
+            }
+        }
+
+        for (name, value) in vars {
+            if name == config_file_var {
+                continue;
+            }
+            let key = name[ENV_PREFIX.len()..].to_lowercase().replace('_', "-");
+            let origin = ConfigSource::Env(name.clone());
+            if !ARGS.contains(&key.as_str()) {
+                return Err(Error::UnknownConfigKey { key, origin });
+            }
+            if key == "data-dir" {
+                // Comma separated, and replaces the data dirs from the config file.
+                config.data_dirs.clear();
+                for value in value.split(',') {
+                    config.set_value(&key, value, origin.clone())?;
+                }
+            } else {
+                config.set_value(&key, &value, origin)?;
+            }
+        }
 
         for arg in &ARGS {
             let occurrences = command_line_args.occurrences_of(arg);
             if occurrences != 0 {
-                if let Some(cla) = command_line_args.value_of(arg) {
-                    config.set_value(arg, cla)?;
+                let origin = ConfigSource::CommandLine;
+                if *arg == "data-dir" {
+                    // Replaces, rather than adds to, the data dirs from the config file.
+                    config.data_dirs.clear();
+                    for cla in command_line_args.values_of(arg).into_iter().flatten() {
+                        config.set_value(arg, cla, origin.clone())?;
+                    }
+                } else if let Some(cla) = command_line_args.value_of(arg) {
+                    config.set_value(arg, cla, origin)?;
                 } else {
-                    config.set_flag(arg, occurrences);
+                    config.set_flag(arg, occurrences, origin)?;
                 }
             }
         }
+        config.config_file = match config_file {
+            _ if fresh => None,
+            Some(path) => Some(path),
+            None => Some(project_dirs()?.join(CONFIG_FILE)),
+        };
+        config.print_config = command_line_args.is_present("print-config");
+        config.command = Config::from_clap(command_line_args).command;
 
         Ok(config)
     }
@@ -145,9 +425,74 @@ impl Config {
         self.local
     }
 
-    /// Upper limit in bytes for allowed network storage on this node.
+    /// Upper limit in bytes for allowed network storage on this node.  If not set, the sum of the
+    /// capacities of the data directories, if any.
     pub fn max_capacity(&self) -> u64 {
-        self.max_capacity.unwrap_or(DEFAULT_MAX_CAPACITY)
+        match self.max_capacity {
+            Some(max_capacity) => max_capacity,
+            None if !self.data_dirs.is_empty() => self
+                .data_dirs
+                .iter()
+                .fold(0, |sum, data_dir| sum.saturating_add(data_dir.capacity)),
+            None => DEFAULT_MAX_CAPACITY,
+        }
+    }
+
+    /// Upper limit in bytes for the space used by blob chunks, if any.
+    pub fn blob_quota(&self) -> Option<u64> {
+        self.blob_quota
+    }
+
+    /// Upper limit in bytes for the space used by maps, if any.
+    pub fn map_quota(&self) -> Option<u64> {
+        self.map_quota
+    }
+
+    /// Upper limit in bytes for the space used by sequences, if any.
+    pub fn sequence_quota(&self) -> Option<u64> {
+        self.sequence_quota
+    }
+
+    /// Number of subdirectory levels chunk files are spread over.
+    pub fn chunk_dir_depth(&self) -> usize {
+        self.chunk_dir_depth.unwrap_or(DEFAULT_CHUNK_DIR_DEPTH)
+    }
+
+    /// Backend the chunks are kept in.
+    pub fn chunk_store_backend(&self) -> ChunkBackend {
+        self.chunk_store_backend.unwrap_or_default()
+    }
+
+    /// Number of stored chunks per minute checked for corruption.
+    pub fn scrub_rate(&self) -> u64 {
+        self.scrub_rate.unwrap_or(DEFAULT_SCRUB_RATE)
+    }
+
+    /// Size in bytes of the cache of recently read chunks.
+    pub fn chunk_cache_size(&self) -> u64 {
+        self.chunk_cache_size.unwrap_or(DEFAULT_CHUNK_CACHE_SIZE)
+    }
+
+    /// Whether to encrypt the data stored on disk.
+    pub fn encrypt_at_rest(&self) -> bool {
+        self.encrypt_at_rest
+    }
+
+    /// Policy the storage of data is priced with.
+    pub fn pricing(&self) -> Pricing {
+        self.pricing.unwrap_or_default()
+    }
+
+    /// Number of client requests per second served per client key and socket address.
+    pub fn client_request_rate(&self) -> u64 {
+        self.client_request_rate
+            .unwrap_or(DEFAULT_CLIENT_REQUEST_RATE)
+    }
+
+    /// Number of client requests served per client key and socket address in a burst.
+    pub fn client_request_burst(&self) -> u64 {
+        self.client_request_burst
+            .unwrap_or(DEFAULT_CLIENT_REQUEST_BURST)
     }
 
     /// Root directory for `ChunkStore`s and cached state. If not set, it defaults to
@@ -160,6 +505,25 @@ impl Config {
         })
     }
 
+    /// Directories to store chunks in, with their capacities.
+    pub fn data_dirs(&self) -> &[DataDir] {
+        &self.data_dirs
+    }
+
+    /// Root directories of the `ChunkStore`s: the data directories, or the root directory if none
+    /// are set.
+    pub fn chunk_dirs(&self) -> Result<Vec<PathBuf>> {
+        if self.data_dirs.is_empty() {
+            Ok(vec![self.root_dir()?])
+        } else {
+            Ok(self
+                .data_dirs
+                .iter()
+                .map(|data_dir| data_dir.path.clone())
+                .collect())
+        }
+    }
+
     /// Set the root directory for `ChunkStore`s and cached state.
     pub fn set_root_dir<P: Into<PathBuf>>(&mut self, path: P) {
         self.root_dir = Some(path.into())
@@ -211,126 +575,278 @@ impl Config {
         self.update_only
     }
 
+    /// The config file read, or to be read once created, if not ignored as the node is fresh.  The
+    /// config of a node without one isn't reloaded on changes.
+    pub fn config_file(&self) -> Option<&Path> {
+        self.config_file.as_deref()
+    }
+
+    /// Print the effective config instead of starting the node?
+    pub fn print_config(&self) -> bool {
+        self.print_config
+    }
+
+    /// Where the value of the config `key` comes from.
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// The effective value of each config key, with the defaults of those not set, and where it
+    /// comes from, one per line.
+    pub fn describe(&self) -> Result<String> {
+        let mut effective = self.clone();
+        effective.max_capacity = Some(self.max_capacity());
+        effective.root_dir = Some(self.root_dir()?);
+        effective.chunk_dir_depth = Some(self.chunk_dir_depth());
+        effective.chunk_store_backend = Some(self.chunk_store_backend());
+        effective.scrub_rate = Some(self.scrub_rate());
+        effective.chunk_cache_size = Some(self.chunk_cache_size());
+        effective.pricing = Some(self.pricing());
+        effective.client_request_rate = Some(self.client_request_rate());
+        effective.client_request_burst = Some(self.client_request_burst());
+        let values = effective.values()?;
+        let lines: Vec<String> = ARGS
+            .iter()
+            .map(|key| {
+                let value = match values.get(*key) {
+                    None | Some(Value::Null) => "not set".to_string(),
+                    Some(Value::String(value)) => value.clone(),
+                    Some(value) => value.to_string(),
+                };
+                format!("{} = {} ({})", key, value, self.source(key))
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    /// The keys the values of which differ in the `other` config.
+    pub fn changes(&self, other: &Config) -> Result<Vec<&'static str>> {
+        let (ours, theirs) = (self.values()?, other.values()?);
+        Ok(ARGS
+            .iter()
+            .copied()
+            .filter(|key| ours.get(*key) != theirs.get(*key))
+            .collect())
+    }
+
+    /// The values of the config, by key.
+    fn values(&self) -> Result<BTreeMap<String, Value>> {
+        Ok(match serde_json::to_value(self)? {
+            Value::Object(object) => flatten(object).into_iter().collect(),
+            _ => BTreeMap::new(),
+        })
+    }
+
+    /// Takes the value of the config `key` from `other`, with where it comes from.  Only the keys
+    /// which can be changed without a restart are taken, i.e. max-capacity, verbose and wallet-id.
+    pub(crate) fn apply(&mut self, key: &str, other: &Config) {
+        match key {
+            "max-capacity" => self.max_capacity = other.max_capacity,
+            "verbose" => self.verbose = other.verbose,
+            "wallet-id" => self.wallet_id = other.wallet_id.clone(),
+            _ => return,
+        }
+        match other.sources.get(key) {
+            Some(source) => {
+                let _ = self.sources.insert(key.to_string(), source.clone());
+            }
+            None => {
+                let _ = self.sources.remove(key);
+            }
+        }
+    }
+
+    /// Maintenance command to run instead of starting the node, if any.
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
     /// Set the Quic-P2P `ip` configuration to 127.0.0.1.
     pub fn listen_on_loopback(&mut self) {
         self.network_config.ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
     }
 
-    pub(crate) fn set_value(&mut self, arg: &str, value: &str) -> Result<(), Error> {
-        if arg == ARGS[0] {
-            self.wallet_id =
-                Some(value.parse().map_err(|e: Infallible| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[1] {
-            self.max_capacity =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[2] {
-            self.root_dir =
-                Some(value.parse().map_err(|e: Infallible| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[3] {
-            self.verbose = value
-                .parse()
-                .map_err(|e: ParseIntError| Error::Logic(format!("Config file error: {:?}", e)))?;
-        } else if arg == ARGS[4] {
-            self.network_config.hard_coded_contacts = serde_json::from_str(value)
-                .map_err(|e| Error::Logic(format!("Config file error: {:?}", e)))?;
-        } else if arg == ARGS[5] {
-            self.network_config.port =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[6] {
-            self.network_config.ip = Some(value.parse().map_err(|e: AddrParseError| {
-                Error::Logic(format!("Config file error: {:?}", e))
-            })?);
-        } else if arg == ARGS[11] {
-            self.completions =
-                Some(value.parse().map_err(|e: Infallible| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[12] {
-            self.log_dir =
-                Some(value.parse().map_err(|e: Infallible| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[7] {
-            self.network_config.max_msg_size_allowed =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[8] {
-            self.network_config.idle_timeout_msec =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[9] {
-            self.network_config.keep_alive_interval_msec =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else if arg == ARGS[15] {
-            self.network_config.upnp_lease_duration =
-                Some(value.parse().map_err(|e: ParseIntError| {
-                    Error::Logic(format!("Config file error: {:?}", e))
-                })?);
-        } else {
-            println!("ERROR");
+    /// Sets the config key `arg` to `value`, as read from `origin`.
+    pub(crate) fn set_value(&mut self, arg: &str, value: &str, origin: ConfigSource) -> Result<()> {
+        let invalid = |reason: String| Error::InvalidConfigValue {
+            key: arg.to_string(),
+            value: value.to_string(),
+            origin: origin.clone(),
+            reason,
+        };
+        let parse_int = |e: ParseIntError| invalid(e.to_string());
+        match arg {
+            "wallet-id" => self.wallet_id = Some(value.to_string()),
+            "max-capacity" => self.max_capacity = Some(value.parse().map_err(parse_int)?),
+            "root-dir" => self.root_dir = Some(PathBuf::from(value)),
+            "verbose" => self.verbose = value.parse().map_err(parse_int)?,
+            "hard-coded-contacts" => {
+                self.network_config.hard_coded_contacts =
+                    serde_json::from_str(value).map_err(|e| invalid(e.to_string()))?
+            }
+            "port" => self.network_config.port = Some(value.parse().map_err(parse_int)?),
+            "ip" => {
+                self.network_config.ip = Some(value.parse().map_err(|e| invalid(format!("{}", e)))?)
+            }
+            "max-msg-size-allowed" => {
+                self.network_config.max_msg_size_allowed = Some(value.parse().map_err(parse_int)?)
+            }
+            "idle-timeout-msec" => {
+                self.network_config.idle_timeout_msec = Some(value.parse().map_err(parse_int)?)
+            }
+            "keep-alive-interval-msec" => {
+                self.network_config.keep_alive_interval_msec =
+                    Some(value.parse().map_err(parse_int)?)
+            }
+            "bootstrap-cache-dir" => {
+                self.network_config.bootstrap_cache_dir = Some(value.to_string())
+            }
+            "upnp-lease-duration" => {
+                self.network_config.upnp_lease_duration = Some(value.parse().map_err(parse_int)?)
+            }
+            "completions" => self.completions = Some(value.to_string()),
+            "log-dir" => self.log_dir = Some(PathBuf::from(value)),
+            "chunk-dir-depth" => self.chunk_dir_depth = Some(value.parse().map_err(parse_int)?),
+            "chunk-store-backend" => {
+                self.chunk_store_backend =
+                    Some(value.parse().map_err(|e: Error| invalid(e.to_string()))?)
+            }
+            "scrub-rate" => self.scrub_rate = Some(value.parse().map_err(parse_int)?),
+            "chunk-cache-size" => self.chunk_cache_size = Some(value.parse().map_err(parse_int)?),
+            "blob-quota" => self.blob_quota = Some(value.parse().map_err(parse_int)?),
+            "map-quota" => self.map_quota = Some(value.parse().map_err(parse_int)?),
+            "sequence-quota" => self.sequence_quota = Some(value.parse().map_err(parse_int)?),
+            "data-dir" => self
+                .data_dirs
+                .push(value.parse().map_err(|e: Error| invalid(e.to_string()))?),
+            "pricing" => {
+                self.pricing = Some(value.parse().map_err(|e: Error| invalid(e.to_string()))?)
+            }
+            "client-request-rate" => {
+                self.client_request_rate = Some(value.parse().map_err(parse_int)?)
+            }
+            "client-request-burst" => {
+                self.client_request_burst = Some(value.parse().map_err(parse_int)?)
+            }
+            flag if FLAGS.contains(&flag) => {
+                let set: bool = value.parse().map_err(|e| invalid(format!("{}", e)))?;
+                return self.set_flag(flag, set as u64, origin);
+            }
+            _ => {
+                return Err(Error::UnknownConfigKey {
+                    key: arg.to_string(),
+                    origin,
+                })
+            }
         }
+        let _ = self.sources.insert(arg.to_string(), origin);
         Ok(())
     }
 
-    pub(crate) fn set_flag(&mut self, arg: &str, occurrences: u64) {
-        if arg == ARGS[3] {
-            self.verbose = occurrences;
-        } else if arg == ARGS[10] {
-            self.first = occurrences >= 1;
-        } else if arg == ARGS[13] {
-            self.update = occurrences >= 1;
-        } else if arg == ARGS[14] {
-            self.update_only = occurrences >= 1;
-        } else if arg == ARGS[16] {
-            self.local = occurrences >= 1;
-        } else if arg == ARGS[17] {
-            self.network_config.fresh = occurrences >= 1;
-        } else if arg == ARGS[18] {
-            self.network_config.clean = occurrences >= 1;
-        } else {
-            println!("ERROR");
+    /// Sets the config flag `arg`, given `occurrences` times on the command line, as read from
+    /// `origin`.
+    pub(crate) fn set_flag(
+        &mut self,
+        arg: &str,
+        occurrences: u64,
+        origin: ConfigSource,
+    ) -> Result<()> {
+        match arg {
+            "verbose" => self.verbose = occurrences,
+            "first" => self.first = occurrences >= 1,
+            "update" => self.update = occurrences >= 1,
+            "update-only" => self.update_only = occurrences >= 1,
+            "local" => self.local = occurrences >= 1,
+            "fresh" => self.network_config.fresh = occurrences >= 1,
+            "clean" => self.network_config.clean = occurrences >= 1,
+            "encrypt-at-rest" => self.encrypt_at_rest = occurrences >= 1,
+            "forward-port" => self.network_config.forward_port = occurrences >= 1,
+            _ => {
+                return Err(Error::UnknownConfigKey {
+                    key: arg.to_string(),
+                    origin,
+                })
+            }
+        }
+        let _ = self.sources.insert(arg.to_string(), origin);
+        Ok(())
+    }
+
+    /// Sets the values of the config file at `path`, parsed into `contents`.
+    fn set_file_values(&mut self, path: &Path, contents: Value) -> Result<()> {
+        let origin = ConfigSource::File(path.to_path_buf());
+        let object = match contents {
+            Value::Object(object) => object,
+            _ => {
+                return Err(Error::InvalidConfigFile {
+                    path: path.to_path_buf(),
+                    reason: "expected a table of keys and values".to_string(),
+                })
+            }
+        };
+        for (key, value) in flatten(object) {
+            let invalid = |value: &Value, reason: &str| Error::InvalidConfigValue {
+                key: key.clone(),
+                value: value.to_string(),
+                origin: origin.clone(),
+                reason: reason.to_string(),
+            };
+            match value {
+                Value::Null => (),
+                Value::Bool(set) if FLAGS.contains(&key.as_str()) => {
+                    self.set_flag(&key, set as u64, origin.clone())?
+                }
+                Value::String(value) => self.set_value(&key, &value, origin.clone())?,
+                Value::Number(value) => self.set_value(&key, &value.to_string(), origin.clone())?,
+                Value::Array(values) if key == "data-dir" => {
+                    for value in values {
+                        let data_dir = match value {
+                            Value::String(data_dir) => data_dir,
+                            value => serde_json::from_value::<DataDir>(value.clone())
+                                .map_err(|e| invalid(&value, &e.to_string()))?
+                                .to_string(),
+                        };
+                        self.set_value(&key, &data_dir, origin.clone())?;
+                    }
+                }
+                Value::Array(_) if key == "hard-coded-contacts" => {
+                    self.set_value(&key, &value.to_string(), origin.clone())?
+                }
+                value if ARGS.contains(&key.as_str()) => {
+                    return Err(invalid(&value, "unexpected type of value"))
+                }
+                _ => return Err(Error::UnknownConfigKey { key, origin }),
+            }
         }
+        Ok(())
     }
 
     fn clear_from_disk() -> Result<()> {
         let path = project_dirs()?.join(CONFIG_FILE);
         if path.exists() {
-            std::fs::remove_file(path)?;
+            fs::remove_file(path)?;
         }
         Ok(())
     }
 
-    /// Reads the default node config file.
-    fn read_from_file() -> Result<Option<Config>> {
-        let path = project_dirs()?.join(CONFIG_FILE);
-
-        match File::open(&path) {
-            Ok(file) => {
-                debug!("Reading settings from {}", path.display());
-                let reader = BufReader::new(file);
-                let config = serde_json::from_reader(reader)?;
-                Ok(config)
-            }
-            Err(error) => {
-                if error.kind() == std::io::ErrorKind::NotFound {
-                    debug!("No config file available at {}", path.display());
-                    Ok(None)
-                } else {
-                    Err(error.into())
-                }
+    /// Reads the config file at `path`, in TOML if its extension is "toml", or in JSON otherwise.
+    fn read_file(path: &Path) -> Result<Value> {
+        let contents = fs::read_to_string(path)?;
+        debug!("Reading settings from {}", path.display());
+        let invalid = |reason: String| Error::InvalidConfigFile {
+            path: path.to_path_buf(),
+            reason,
+        };
+        match path.extension() {
+            Some(extension) if extension == "toml" => {
+                let contents: toml::Value =
+                    toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+                serde_json::to_value(contents).map_err(|e| invalid(e.to_string()))
             }
+            _ => serde_json::from_str(&contents).map_err(|e| invalid(e.to_string())),
         }
     }
 
@@ -369,6 +885,27 @@ where
     Ok(path)
 }
 
+/// The name of the environment variable setting the config `key`.
+fn env_var_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase().replace('-', "_"))
+}
+
+/// The values of a serialised `Config`, by config key, with those of the network config inlined.
+fn flatten(object: serde_json::Map<String, Value>) -> Vec<(String, Value)> {
+    let mut values = Vec::new();
+    for (name, value) in object {
+        let key = match name.replace('_', "-").as_str() {
+            "data-dirs" => "data-dir".to_string(),
+            key => key.to_string(),
+        };
+        match value {
+            Value::Object(object) if key == "network-config" => values.extend(flatten(object)),
+            value => values.push((key, value)),
+        }
+    }
+    values
+}
+
 fn project_dirs() -> Result<PathBuf> {
     let mut home_dir = dirs_next::home_dir()
         .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Home directory not found"))?;
@@ -382,9 +919,14 @@ fn project_dirs() -> Result<PathBuf> {
 #[cfg(test)]
 mod test {
     use super::ARGS;
-    use super::{Config, Error, Result};
-    use std::{fs::File, io::Read, path::Path};
+    use super::{Config, ConfigSource, Error, Result};
+    use std::{
+        fs::{self, File},
+        io::Read,
+        path::Path,
+    };
     use structopt::StructOpt;
+    use tempdir::TempDir;
 
     #[test]
     fn smoke() -> Result<()> {
@@ -409,6 +951,20 @@ mod test {
             ["fresh", "None"],
             ["clean", "None"],
             ["upnp-lease-duration", "180"],
+            ["chunk-dir-depth", "3"],
+            ["chunk-store-backend", "kv"],
+            ["scrub-rate", "10"],
+            ["chunk-cache-size", "1024"],
+            ["blob-quota", "1"],
+            ["map-quota", "1"],
+            ["sequence-quota", "1"],
+            ["data-dir", "disk=1"],
+            ["encrypt-at-rest", "None"],
+            ["pricing", "linear"],
+            ["client-request-rate", "10"],
+            ["client-request-burst", "20"],
+            ["bootstrap-cache-dir", "dir"],
+            ["forward-port", "None"],
         ];
 
         for arg in &ARGS {
@@ -429,7 +985,19 @@ mod test {
                 local: false,
                 wallet_id: None,
                 max_capacity: None,
+                blob_quota: None,
+                map_quota: None,
+                sequence_quota: None,
                 root_dir: None,
+                data_dirs: vec![],
+                chunk_dir_depth: None,
+                chunk_store_backend: None,
+                scrub_rate: None,
+                chunk_cache_size: None,
+                encrypt_at_rest: false,
+                pricing: None,
+                client_request_rate: None,
+                client_request_burst: None,
                 verbose: 0,
                 network_config: Default::default(),
                 first: false,
@@ -437,18 +1005,150 @@ mod test {
                 log_dir: None,
                 update: false,
                 update_only: false,
+                config_file: None,
+                print_config: false,
+                command: None,
+                sources: Default::default(),
             };
             let empty_config = config.clone();
             if let Some(val) = matches.value_of(arg) {
-                config.set_value(arg, val)?;
+                config.set_value(arg, val, ConfigSource::CommandLine)?;
             } else {
-                config.set_flag(arg, occurrences);
+                config.set_flag(arg, occurrences, ConfigSource::CommandLine)?;
             }
+            assert_eq!(ConfigSource::CommandLine, config.source(arg));
+            config.sources.clear();
             assert_ne!(empty_config, config, "Failed to set_value() for {}", arg);
         }
         Ok(())
     }
 
+    #[test]
+    fn sources_are_layered() -> Result<()> {
+        let dir = TempDir::new("config")?;
+        let path = dir.path().join("node.toml");
+        fs::write(
+            &path,
+            "max_capacity = 10\nscrub_rate = 20\nencrypt_at_rest = true\n\n\
+             [network_config]\nport = 1000\n",
+        )?;
+        let matches = Config::clap().get_matches_from(&[
+            "sn_node",
+            "--config-file",
+            &path.display().to_string(),
+            "--scrub-rate",
+            "30",
+        ]);
+        let vars = vec![
+            ("SN_NODE_MAX_CAPACITY".to_string(), "15".to_string()),
+            ("SN_NODE_SCRUB_RATE".to_string(), "25".to_string()),
+            ("HOME".to_string(), "/home".to_string()),
+        ];
+        let config = Config::from_sources(&matches, vars)?;
+
+        assert_eq!(15, config.max_capacity());
+        assert_eq!(30, config.scrub_rate());
+        assert!(config.encrypt_at_rest());
+        assert_eq!(Some(1000), config.network_config().port);
+        assert_eq!(
+            ConfigSource::Env("SN_NODE_MAX_CAPACITY".to_string()),
+            config.source("max-capacity")
+        );
+        assert_eq!(ConfigSource::CommandLine, config.source("scrub-rate"));
+        assert_eq!(ConfigSource::File(path.clone()), config.source("port"));
+        assert_eq!(ConfigSource::Default, config.source("map-quota"));
+        Ok(())
+    }
+
+    #[test]
+    fn changes_are_listed_by_key() -> Result<()> {
+        let config = Config::default();
+        let mut changed = config.clone();
+        changed.set_value("max-capacity", "10", ConfigSource::CommandLine)?;
+        changed.set_value("port", "1000", ConfigSource::CommandLine)?;
+        changed.set_flag("verbose", 2, ConfigSource::CommandLine)?;
+
+        assert_eq!(
+            vec!["max-capacity", "verbose", "port"],
+            config.changes(&changed)?
+        );
+        assert!(changed.changes(&changed.clone())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn only_keys_changeable_without_a_restart_are_applied() -> Result<()> {
+        let mut config = Config::default();
+        let mut changed = config.clone();
+        changed.set_value("max-capacity", "10", ConfigSource::CommandLine)?;
+        changed.set_value("port", "1000", ConfigSource::CommandLine)?;
+        for key in changed.changes(&config)? {
+            config.apply(key, &changed);
+        }
+
+        assert_eq!(10, config.max_capacity());
+        assert_eq!(ConfigSource::CommandLine, config.source("max-capacity"));
+        assert_eq!(vec!["port"], config.changes(&changed)?);
+        assert_eq!(ConfigSource::Default, config.source("port"));
+
+        // Unset keys are unset, back to their defaults.
+        config.apply("max-capacity", &Config::default());
+        assert_eq!(ConfigSource::Default, config.source("max-capacity"));
+        assert!(config.changes(&Config::default())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_and_invalid_keys_are_rejected() -> Result<()> {
+        let dir = TempDir::new("config")?;
+        let path = dir.path().join("node.config");
+        fs::write(&path, r#"{ "max_capacity": 10, "max_capacty": 10 }"#)?;
+        let matches = Config::clap().get_matches_from(&[
+            "sn_node",
+            "--config-file",
+            &path.display().to_string(),
+        ]);
+        match Config::from_sources(&matches, vec![]) {
+            Err(Error::UnknownConfigKey { key, origin }) => {
+                assert_eq!("max-capacty", key);
+                assert_eq!(ConfigSource::File(path.clone()), origin);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // As are those in the environment, but not the config file variable.
+        let matches = Config::clap().get_matches_from(&["sn_node", "--fresh"]);
+        let vars = vec![
+            ("SN_NODE_SCRUB_RATE".to_string(), "25".to_string()),
+            (
+                "SN_NODE_CONFIG_FILE".to_string(),
+                path.display().to_string(),
+            ),
+        ];
+        let config = Config::from_sources(&matches, vars.clone())?;
+        assert_eq!(25, config.scrub_rate());
+
+        let mut misspelt = vars;
+        misspelt.push(("SN_NODE_SCRUB_RAT".to_string(), "fast".to_string()));
+        match Config::from_sources(&matches, misspelt) {
+            Err(Error::UnknownConfigKey { key, origin }) => {
+                assert_eq!("scrub-rat", key);
+                assert_eq!(ConfigSource::Env("SN_NODE_SCRUB_RAT".to_string()), origin);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        let vars = vec![("SN_NODE_SCRUB_RATE".to_string(), "fast".to_string())];
+        match Config::from_sources(&matches, vars) {
+            Err(Error::InvalidConfigValue { key, origin, .. }) => {
+                assert_eq!("scrub-rate", key);
+                assert_eq!(ConfigSource::Env("SN_NODE_SCRUB_RATE".to_string()), origin);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        Ok(())
+    }
+
     #[ignore]
     #[test]
     fn parse_sample_config_file() -> Result<(), Error> {
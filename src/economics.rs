@@ -0,0 +1,33 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The formulas of the network economics, i.e. the cost of storing data and the rewards of nodes,
+//! as used by the Elders, for modelling them outside of a running network (see `sn_economics`).
+
+pub use crate::capacity::{CurvePricing, LinearPricing, Pricing, PricingPolicy};
+use sn_data_types::Token;
+use std::convert::TryFrom;
+
+/// The share of the token supply of a section with a prefix of `prefix_len` bits, in nanos.
+pub fn max_section_nanos(prefix_len: usize) -> u64 {
+    crate::capacity::max_section_nanos(prefix_len)
+}
+
+/// The reward of a node of `age`, in a section with a prefix of `prefix_len` bits.  Rewards too
+/// large to be expressed in nanos are capped at the largest amount that can.
+pub fn reward(age: u8, prefix_len: usize) -> Token {
+    let time = 2_u64.saturating_pow(age as u32);
+    let nanos = 1_000_000_000;
+    // A network of more than u64::MAX sections leaves nothing of the reward to share.
+    let share = u32::try_from(prefix_len)
+        .ok()
+        .and_then(|prefix_len| 2_u64.checked_pow(prefix_len))
+        .map_or(0, |network_size| time.saturating_mul(nanos) / network_size);
+    let steepness_reductor = (prefix_len as u64).saturating_add(1);
+    Token::from_nano(share.saturating_mul(steepness_reductor))
+}
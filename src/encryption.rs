@@ -0,0 +1,201 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encryption at rest of the chunks and databases a node keeps on disk.
+
+use crate::{utils, Error, Result};
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use log::info;
+use pickledb::PickleDb;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::{self, Debug, Formatter};
+
+/// Size in bytes of an `EncryptionKey`.
+pub(crate) const KEY_SIZE: usize = 32;
+
+/// Size in bytes of the random nonce each encrypted value is prefixed with.
+const NONCE_SIZE: usize = 24;
+
+/// A node-local secret key, which data is encrypted with before being written to disk.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    bytes: [u8; KEY_SIZE],
+}
+
+impl EncryptionKey {
+    /// Generates a new random key.
+    pub(crate) fn random() -> Self {
+        Self {
+            bytes: rand::thread_rng().gen(),
+        }
+    }
+
+    pub(crate) fn from_bytes(bytes: [u8; KEY_SIZE]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn to_bytes(&self) -> [u8; KEY_SIZE] {
+        self.bytes
+    }
+
+    /// Encrypts `plaintext`, prefixing the result with the random nonce used.
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; NONCE_SIZE] = rand::thread_rng().gen();
+        let ciphertext = self
+            .cipher()
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| Error::Encryption)?;
+        let mut contents = nonce.to_vec();
+        contents.extend(ciphertext);
+        Ok(contents)
+    }
+
+    /// Decrypts `contents` previously returned by `encrypt`.  Fails with `Error::Encryption` if
+    /// they weren't encrypted with this key, or have been tampered with.
+    pub(crate) fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>> {
+        if contents.len() < NONCE_SIZE {
+            return Err(Error::Encryption);
+        }
+        let (nonce, ciphertext) = contents.split_at(NONCE_SIZE);
+        self.cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Encryption)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.bytes))
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "EncryptionKey(..)")
+    }
+}
+
+/// A `PickleDb` whose values are encrypted at rest, if it is given a key.  The db keys themselves,
+/// which are ids and addresses, are stored as they are.
+pub struct Db {
+    db: PickleDb,
+    key: Option<EncryptionKey>,
+}
+
+impl Db {
+    /// Wraps `db`, encrypting the values written to it with `key`, if any.
+    pub(crate) fn new(db: PickleDb, key: Option<EncryptionKey>) -> Self {
+        Self { db, key }
+    }
+
+    /// Returns the value stored under `key`, if any, and if it can be read as a `V`.
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
+        match &self.key {
+            Some(encryption_key) => {
+                let contents = encryption_key.decrypt(&self.db.get::<Vec<u8>>(key)?).ok()?;
+                utils::deserialise(&contents).ok()
+            }
+            None => self.db.get(key),
+        }
+    }
+
+    /// Stores `value` under `key`, replacing any previous value.
+    pub fn set<V: Serialize>(&mut self, key: &str, value: &V) -> Result<()> {
+        match &self.key {
+            Some(encryption_key) => {
+                let contents = encryption_key.encrypt(&utils::serialise(value)?)?;
+                self.db.set(key, &contents)?;
+            }
+            None => self.db.set(key, value)?,
+        }
+        Ok(())
+    }
+
+    /// Removes the value stored under `key`, returning whether there was one.
+    pub fn rem(&mut self, key: &str) -> Result<bool> {
+        Ok(self.db.rem(key)?)
+    }
+
+    /// Whether a value is stored under `key`.
+    pub fn exists(&self, key: &str) -> bool {
+        self.db.exists(key)
+    }
+
+    /// Lists all keys.
+    pub fn get_all(&self) -> Vec<String> {
+        self.db.get_all()
+    }
+
+    /// Number of keys.
+    pub fn total_keys(&self) -> usize {
+        self.db.total_keys()
+    }
+
+    /// Encrypts the values still stored in plaintext, from before encryption was enabled, reading
+    /// them as `V`s.  Does nothing if the db isn't encrypted.
+    pub(crate) fn migrate<V: Serialize + DeserializeOwned>(&mut self) -> Result<()> {
+        let encryption_key = match &self.key {
+            Some(encryption_key) => encryption_key.clone(),
+            None => return Ok(()),
+        };
+        let mut count = 0;
+        for key in self.db.get_all() {
+            let is_encrypted = match self.db.get::<Vec<u8>>(&key) {
+                Some(contents) => encryption_key.decrypt(&contents).is_ok(),
+                None => false,
+            };
+            if is_encrypted {
+                continue;
+            }
+            if let Some(value) = self.db.get::<V>(&key) {
+                self.set(&key, &value)?;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            info!("Encrypted {} plaintext db values", count);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Db, EncryptionKey};
+    use crate::{utils, Result};
+    use tempdir::TempDir;
+
+    #[test]
+    fn plaintext_db_values_are_migrated() -> Result<()> {
+        let root = TempDir::new("encryption")?;
+        let mut db = Db::new(utils::new_auto_dump_db(root.path(), "test.db")?, None);
+        db.set("plain", &"value".to_string())?;
+        drop(db);
+
+        let key = EncryptionKey::random();
+        let mut db = Db::new(utils::new_auto_dump_db(root.path(), "test.db")?, Some(key));
+        assert_eq!(None, db.get::<String>("plain"));
+        db.migrate::<String>()?;
+        assert_eq!(Some("value".to_string()), db.get("plain"));
+        drop(db);
+
+        // Unreadable without the key.
+        let db = Db::new(utils::new_auto_dump_db(root.path(), "test.db")?, None);
+        assert_ne!(Some("value".to_string()), db.get::<String>("plain"));
+        let other_key = EncryptionKey::random();
+        let db = Db::new(
+            utils::new_auto_dump_db(root.path(), "test.db")?,
+            Some(other_key),
+        );
+        assert_eq!(None, db.get::<String>("plain"));
+
+        Ok(())
+    }
+}
@@ -7,9 +7,10 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 // use bls::PublicKey;
-use sn_data_types::{Error as DtError, PublicKey};
+use crate::{chunk_store::ChunkBackend, config_handler::ConfigSource};
+use sn_data_types::{Error as DtError, PublicKey, Token};
 use sn_messaging::{Error as ErrorMessage, MessageId};
-use std::io;
+use std::{io, path::PathBuf, time::Duration};
 use thiserror::Error;
 #[allow(clippy::large_enum_variant)]
 #[derive(Error, Debug)]
@@ -35,6 +36,33 @@ pub enum Error {
     /// Chunk Store Id could not be found
     #[error("Could not fetch StoreId")]
     NoStoreId,
+    /// The requested number of chunk subdirectory levels is not supported.
+    #[error("Unsupported chunk dir depth: {0}")]
+    InvalidChunkDirDepth(usize),
+    /// A store was written by another kind of backend than the one configured.
+    #[error(
+        "Chunk store at {} was written by the {} backend, not {}",
+        .dir.display(),
+        .found,
+        .configured
+    )]
+    ChunkBackendMismatch {
+        /// The store directory.
+        dir: PathBuf,
+        /// The backend the store was written by.
+        found: ChunkBackend,
+        /// The backend configured.
+        configured: ChunkBackend,
+    },
+    /// Data at rest could not be encrypted, or decrypted (e.g. for lack of the key).
+    #[error("Encryption at rest error")]
+    Encryption,
+    /// An archive to import is malformed, or doesn't match its manifest.
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    /// The root dir was written by a newer node, in a format this one can't read.
+    #[error("On-disk format version {0} is newer than the supported version {1}")]
+    UnsupportedFormatVersion(u32, u32),
     /// Threshold crypto combine signatures error
     #[error("Could not combine signatures")]
     CouldNotCombineSignatures,
@@ -56,6 +84,9 @@ pub enum Error {
     /// PickleDb error.
     #[error("PickleDb error:: {0}")]
     PickleDb(#[from] pickledb::error::Error),
+    /// Sled error.
+    #[error("Sled error:: {0}")]
+    Sled(#[from] sled::Error),
     /// NetworkData error.
     #[error("Network data error:: {0}")]
     NetworkData(#[from] sn_data_types::Error),
@@ -86,6 +117,48 @@ pub enum Error {
     /// Data operation is invalid, eg private operation on public data
     #[error("Invalid operation")]
     InvalidOperation,
+    /// The payment for a data write doesn't cover its store cost.
+    #[error("Too low payment: {paid}, expected: {expected}")]
+    TooLowPayment {
+        /// The amount paid.
+        paid: Token,
+        /// The store cost of the write.
+        expected: Token,
+    },
+    /// A config file can't be read or parsed.
+    #[error("Invalid config file {}: {reason}", path.display())]
+    InvalidConfigFile {
+        /// The path of the config file.
+        path: PathBuf,
+        /// Why it can't be read or parsed.
+        reason: String,
+    },
+    /// A config source sets a key that doesn't exist.
+    #[error("Unknown config key {key:?} in {origin}")]
+    UnknownConfigKey {
+        /// The unknown key.
+        key: String,
+        /// Where the key is set.
+        origin: ConfigSource,
+    },
+    /// A config source sets a key to an invalid value.
+    #[error("Invalid value {value:?} for config key {key:?} in {origin}: {reason}")]
+    InvalidConfigValue {
+        /// The key set.
+        key: String,
+        /// The invalid value.
+        value: String,
+        /// Where the key is set.
+        origin: ConfigSource,
+        /// Why the value is invalid.
+        reason: String,
+    },
+    /// A client has sent more requests than it is allowed to.
+    #[error("Too many requests, retry after {retry_after:?}")]
+    Throttled {
+        /// How long the client is to wait before retrying.
+        retry_after: Duration,
+    },
     /// No mapping to sn_messages::Error could be found. Either we need a new error there, or we need to handle or convert this error before sending it as a message
     #[error("No mapping to sn_messages error is set up for this NodeError {0}")]
     NoErrorMapping(String),
@@ -105,6 +178,10 @@ pub(crate) fn convert_to_error_message(error: Error) -> Result<sn_messaging::Err
         Error::BalanceExists => Ok(ErrorMessage::BalanceExists),
         Error::TempDirCreationFailed(_) => Ok(ErrorMessage::FailedToWriteFile),
         Error::DataExists => Ok(ErrorMessage::DataExists),
+        // There is no error carrying the store cost; clients get it with `GetStoreCost`.
+        Error::TooLowPayment { .. } => Ok(ErrorMessage::InsufficientBalance),
+        // There is no error carrying when to retry, so clients are to back off on their own.
+        Error::Throttled { .. } => Ok(ErrorMessage::InvalidOperation),
         Error::NetworkData(error) => convert_dt_error_to_error_message(error),
         error => Err(Error::NoErrorMapping(error.to_string())),
     }
@@ -29,24 +29,33 @@
 // For quick_error
 #![recursion_limit = "128"]
 
+mod archive;
 mod capacity;
 mod chaos;
 mod chunk_store;
 mod config_handler;
+mod encryption;
 mod error;
 mod network;
 mod network_state;
 mod node;
 mod to_db_key;
 
+pub mod economics;
 pub mod utils;
 
 pub(crate) use to_db_key::ToDbKey;
 
 pub use crate::{
-    config_handler::{write_connection_info, Config},
+    archive::{export_archive, import_archive, ArchiveManifest, ArchivedFile},
+    capacity::Pricing,
+    chunk_store::{
+        reconcile_used_space, ChunkBackend, ChunkStoreStats, KindSpaceStats, SizeBucket,
+        StorageStats, UsedSpaceReport, UsedSpaceStats, VolumeSpaceStats,
+    },
+    config_handler::{write_connection_info, Command, Config, ConfigSource, DataDir},
     error::{Error, Result},
     network::Network,
     network_state::{AdultState, ElderState, NodeInfo, NodeState},
-    node::Node,
+    node::{migrate_dirs, Node, ThrottleStats},
 };
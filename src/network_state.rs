@@ -21,7 +21,12 @@
 // What things do we _need_ to access most current state of?
 // - ..
 
-use crate::{Network, Result};
+use crate::{
+    capacity::Pricing,
+    chunk_store::{BackendOptions, ChunkBackend, UsedSpace},
+    encryption::EncryptionKey,
+    Network, Result,
+};
 use bls::{PublicKeySet, PublicKeyShare};
 use ed25519_dalek::PublicKey as Ed25519PublicKey;
 use itertools::Itertools;
@@ -363,10 +368,35 @@ pub struct NodeInfo {
     pub node_id: PublicKey,
     ///
     pub root_dir: PathBuf,
-    /// Upper limit in bytes for allowed network storage on this node.
+    /// Root directories of the `ChunkStore`s, each e.g. on a disk of its own.
+    pub data_dirs: Vec<PathBuf>,
+    /// Space used by all the stores of this node, bounded by the
+    /// upper limit in bytes for allowed network storage on this node.
     /// An Adult would be using the space for chunks,
     /// while an Elder uses it for metadata.
-    pub max_storage_capacity: u64,
+    pub used_space: UsedSpace,
+    /// Upper limit in bytes for the space used by blob chunks, if any.
+    pub blob_quota: Option<u64>,
+    /// Upper limit in bytes for the space used by maps, if any.
+    pub map_quota: Option<u64>,
+    /// Upper limit in bytes for the space used by sequences, if any.
+    pub sequence_quota: Option<u64>,
+    /// Number of subdirectory levels chunk files are sharded into.
+    pub chunk_dir_depth: usize,
+    /// Backend the chunks are kept in.
+    pub chunk_backend: ChunkBackend,
+    /// Size in bytes of the cache of recently read chunks, 0 if disabled.
+    pub chunk_cache_size: u64,
+    /// Policy the storage of data is priced with, as an Elder.
+    pub pricing: Pricing,
+    /// Number of requests per second served per client key and socket address, as an Elder, 0 if
+    /// unlimited.
+    pub client_request_rate: u64,
+    /// Number of requests served per client key and socket address in a burst, as an Elder.
+    pub client_request_burst: u64,
+    /// The key data is encrypted with before being written to disk, if encryption at rest is
+    /// enabled.
+    pub encryption_key: Option<EncryptionKey>,
     /// The key used by the node to receive earned rewards.
     pub reward_key: PublicKey,
 }
@@ -376,4 +406,12 @@ impl NodeInfo {
     pub fn path(&self) -> &Path {
         self.root_dir.as_path()
     }
+
+    /// What the chunk stores are opened with.
+    pub(crate) fn chunk_store_options(&self) -> BackendOptions {
+        BackendOptions {
+            kind: self.chunk_backend,
+            dir_depth: self.chunk_dir_depth,
+        }
+    }
 }
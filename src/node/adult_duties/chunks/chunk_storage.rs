@@ -8,12 +8,16 @@
 
 //pub use crate::chunk_store::UsedSpace;
 use crate::{
-    chunk_store::{BlobChunkStore, UsedSpace},
+    chunk_store::{BlobChunkStore, ChunkStoreStats, KeysCursor},
     error::convert_to_error_message,
-    node::{msg_wrapping::AdultMsgWrapping, node_ops::NodeMessagingDuty, Error},
+    node::{
+        msg_wrapping::AdultMsgWrapping,
+        node_ops::{IntoNodeOp, NodeMessagingDuty, NodeOperation},
+        Error,
+    },
     AdultState, Result,
 };
-use log::{error, info};
+use log::{error, info, trace, warn};
 use sn_data_types::{Blob, BlobAddress, Signature};
 use sn_messaging::{
     Address, AdultDuties, CmdError, Error as ErrorMessage, Message, MessageId, MsgSender,
@@ -30,15 +34,31 @@ use xor_name::XorName;
 pub(crate) struct ChunkStorage {
     chunks: BlobChunkStore,
     wrapping: AdultMsgWrapping,
+    // Where the current scrubbing pass over the store is at, or `None` to start a new one.
+    scrub_cursor: Option<KeysCursor>,
 }
 
 impl ChunkStorage {
     pub(crate) async fn new(adult_state: AdultState) -> Result<Self> {
         let node_info = adult_state.info();
-        let used_space = UsedSpace::new(node_info.max_storage_capacity);
-        let chunks = BlobChunkStore::new(node_info.path(), used_space).await?;
+        let used_space = node_info.used_space.clone();
+        let chunks = BlobChunkStore::new(
+            &node_info.data_dirs,
+            node_info.chunk_store_options(),
+            used_space,
+        )
+        .await?
+        .with_encryption(node_info.encryption_key.clone())
+        .await?
+        .with_quota(node_info.blob_quota)
+        .await?
+        .with_cache(node_info.chunk_cache_size);
         let wrapping = AdultMsgWrapping::new(adult_state, AdultDuties::ChunkStorage);
-        Ok(Self { chunks, wrapping })
+        Ok(Self {
+            chunks,
+            wrapping,
+            scrub_cursor: None,
+        })
     }
 
     pub(crate) async fn store(
@@ -107,7 +127,7 @@ impl ChunkStorage {
             }
         }
 
-        if self.chunks.has(data.address()) {
+        if self.chunks.has(data.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
@@ -127,7 +147,11 @@ impl ChunkStorage {
         let result = self
             .chunks
             .get(address)
+            .await
             .map_err(|_| ErrorMessage::NoSuchData);
+        if let Some(stats) = self.chunks.cache_stats().await {
+            trace!("{}: Chunk cache: {}", self, stats);
+        }
         // .map_err(|error| error.to_string());
         self.wrapping
             .send_to_section(
@@ -172,7 +196,7 @@ impl ChunkStorage {
         msg_id: MessageId,
         origin: Address,
     ) -> Result<NodeMessagingDuty> {
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)?),
         };
@@ -189,7 +213,7 @@ impl ChunkStorage {
 
     ///
     pub async fn store_for_replication(&mut self, blob: Blob) -> Result<NodeMessagingDuty> {
-        if self.chunks.has(blob.address()) {
+        if self.chunks.has(blob.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
@@ -203,10 +227,54 @@ impl ChunkStorage {
         Ok(NodeMessagingDuty::NoOp)
     }
 
+    /// Re-checks up to `count` stored chunks for corruption, continuing the current pass over the
+    /// store, or starting a new one.  Corrupt chunks are quarantined and reported to the section,
+    /// so that they are replicated from the other holders.
+    pub async fn scrub(&mut self, count: usize) -> Result<NodeOperation> {
+        let page = self
+            .chunks
+            .keys_page(self.scrub_cursor.take(), count, None)
+            .await?;
+        self.scrub_cursor = page.next;
+        let mut ops = Vec::new();
+        for address in page.keys {
+            match self.chunks.verify(&address).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    warn!("{}: Quarantining corrupt chunk: {:?}", self, address);
+                    self.chunks.quarantine(&address).await?;
+                    ops.push(self.report_lost_chunk(address).await.convert());
+                }
+                // Deleted since the pass started.
+                Err(Error::NoSuchChunk) => (),
+                Err(error) => warn!("{}: Could not check chunk {:?}: {}", self, address, error),
+            }
+        }
+        Ok(ops.into())
+    }
+
+    /// Reports the chunk to the section as no longer held by us, for it to be replicated.
+    async fn report_lost_chunk(&self, address: BlobAddress) -> Result<NodeMessagingDuty> {
+        let message = Message::NodeCmdError {
+            id: MessageId::new(),
+            error: NodeCmdError::Data(NodeDataError::ChunkReplication {
+                address,
+                error: ErrorMessage::NoSuchData,
+            }),
+            correlation_id: MessageId::new(),
+            cmd_origin: Address::Section(*address.name()),
+        };
+        self.wrapping.send_to_section(message, true).await
+    }
+
     pub async fn used_space_ratio(&self) -> f64 {
         self.chunks.used_space_ratio().await
     }
 
+    pub async fn stats(&self) -> Result<ChunkStoreStats> {
+        self.chunks.stats().await
+    }
+
     // pub(crate) fn get_for_duplciation(
     //     &self,
     //     address: BlobAddress,
@@ -236,12 +304,12 @@ impl ChunkStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        if !self.chunks.has(&address) {
+        if !self.chunks.has(&address).await {
             info!("{}: Immutable chunk doesn't exist: {:?}", self, address);
             return Ok(NodeMessagingDuty::NoOp);
         }
 
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(Blob::Private(data)) => {
                 let pk = origin.id().public_key();
                 if *data.owner() == pk {
@@ -11,6 +11,7 @@ mod reading;
 mod writing;
 
 use crate::{
+    chunk_store::ChunkStoreStats,
     node::node_ops::{NodeDuty, NodeMessagingDuty, NodeOperation},
     AdultState, Error, Result,
 };
@@ -23,20 +24,28 @@ use sn_messaging::{
 use std::{
     collections::BTreeSet,
     fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
 };
 use xor_name::XorName;
 
 pub const MAX_STORAGE_USAGE_RATIO: f64 = 0.8;
 
+/// How often a full adult reports to its section that it is still full.  Well within
+/// `FULL_NODE_EXPIRY`, after which Elders no longer count it as full.
+const FULL_REPORT_INTERVAL: Duration = Duration::from_secs(2 * 60);
+
 /// Operations on data chunks.
 pub(crate) struct Chunks {
     chunk_storage: ChunkStorage,
+    // When we last reported being full, if we still are.
+    last_full_report: Option<Instant>,
 }
 
 impl Chunks {
     pub async fn new(adult_state: AdultState) -> Result<Self> {
         Ok(Self {
             chunk_storage: ChunkStorage::new(adult_state).await?,
+            last_full_report: None,
         })
     }
 
@@ -63,13 +72,37 @@ impl Chunks {
         }
     }
 
-    pub async fn check_storage(&self) -> Result<NodeOperation> {
-        info!("Checking used storage");
+    /// Reports being full to our section, and keeps doing so every `FULL_REPORT_INTERVAL` while
+    /// we are.  Once we have space again (e.g. after deleting chunks, or our capacity being
+    /// raised), we stop, and our Elders count us as having space when the last report expires.
+    ///
+    /// Run after each chunk write or delete, and periodically.
+    pub async fn check_storage(&mut self) -> Result<NodeOperation> {
+        trace!("Checking used storage");
         if self.chunk_storage.used_space_ratio().await > MAX_STORAGE_USAGE_RATIO {
-            Ok(NodeDuty::StorageFull.into())
-        } else {
-            Ok(NodeOperation::NoOp)
+            let is_due = match self.last_full_report {
+                Some(reported) => reported.elapsed() >= FULL_REPORT_INTERVAL,
+                None => true,
+            };
+            if is_due {
+                self.last_full_report = Some(Instant::now());
+                return Ok(NodeDuty::StorageFull.into());
+            }
+        } else if self.last_full_report.take().is_some() {
+            info!("Storage available");
         }
+        Ok(NodeOperation::NoOp)
+    }
+
+    /// What the chunk store holds, and the operations it has served.
+    pub async fn stats(&self) -> Result<ChunkStoreStats> {
+        self.chunk_storage.stats().await
+    }
+
+    /// Re-checks up to `count` stored chunks for corruption.
+    pub async fn scrub(&mut self, count: usize) -> Result<NodeOperation> {
+        trace!("Scrubbing {} chunks", count);
+        self.chunk_storage.scrub(count).await
     }
 
     ///
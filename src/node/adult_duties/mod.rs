@@ -14,7 +14,7 @@ use crate::{
         AdultDuty, ChunkReplicationCmd, ChunkReplicationDuty, ChunkReplicationQuery,
         ChunkStoreDuty, IntoNodeOp, NodeOperation,
     },
-    AdultState, Result,
+    AdultState, Result, StorageStats,
 };
 use std::fmt::{self, Display, Formatter};
 
@@ -36,6 +36,16 @@ impl AdultDuties {
         &self.state
     }
 
+    /// The stats of the blob store, and of the space used by the node.
+    pub async fn stats(&self) -> Result<StorageStats> {
+        Ok(StorageStats {
+            blobs: Some(self.chunks.stats().await?),
+            maps: None,
+            sequences: None,
+            used_space: self.state.info().used_space.stats().await,
+        })
+    }
+
     pub async fn process_adult_duty(&mut self, duty: AdultDuty) -> Result<NodeOperation> {
         use AdultDuty::*;
         use ChunkReplicationCmd::*;
@@ -49,6 +59,13 @@ impl AdultDuties {
                     let second = self.chunks.check_storage().await;
                     Ok(vec![first, second].into())
                 }
+                ScrubChunks(count) => {
+                    // Quarantined chunks free up space.
+                    let first = self.chunks.scrub(count).await;
+                    let second = self.chunks.check_storage().await;
+                    Ok(vec![first, second].into())
+                }
+                CheckStorage => self.chunks.check_storage().await,
                 ChunkStoreDuty::NoOp => return Ok(NodeOperation::NoOp),
             },
             RunAsChunkReplication(replication_duty) => match replication_duty {
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    capacity::ChunkHolderDbs,
+    capacity::{Capacity, ChunkHolderDbs, HolderMetadata, MAX_CHUNK_SIZE},
     error::convert_to_error_message,
     node::msg_wrapping::ElderMsgWrapping,
     node::node_ops::{NodeMessagingDuty, NodeOperation},
@@ -36,11 +36,6 @@ struct ChunkMetadata {
     owner: Option<PublicKey>,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-struct HolderMetadata {
-    chunks: BTreeSet<BlobAddress>,
-}
-
 /// Operations over the data type Blob.
 pub(super) struct BlobRegister {
     dbs: ChunkHolderDbs,
@@ -54,6 +49,8 @@ impl BlobRegister {
         wrapping: ElderMsgWrapping,
         elder_state: ElderState,
     ) -> Result<Self> {
+        dbs.metadata.borrow_mut().migrate::<ChunkMetadata>()?;
+        dbs.holders.borrow_mut().migrate::<HolderMetadata>()?;
         Ok(Self {
             dbs,
             elder_state,
@@ -235,7 +232,7 @@ impl BlobRegister {
 
         if let Err(error) = self.dbs.metadata.borrow_mut().set(&db_key, &metadata) {
             warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-            return Err(error.into());
+            return Err(error);
         }
 
         // We're acting as data handler, received request from client handlers
@@ -249,7 +246,7 @@ impl BlobRegister {
             .set(&holder.to_db_key()?, &holders_metadata)
         {
             warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-            return Err(error.into());
+            return Err(error);
         }
         Ok(())
     }
@@ -321,6 +318,31 @@ impl BlobRegister {
         Ok(cmds.into())
     }
 
+    pub(super) async fn replicate_lost_chunk(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeOperation> {
+        trace!(
+            "Replicating chunk {:?} lost by holder {:?}",
+            address,
+            holder
+        );
+
+        self.remove_chunk_holder(address, holder)?;
+        let current_holders = match self.get_metadata_for(address) {
+            Ok(metadata) => metadata.holders,
+            Err(_) => {
+                warn!("{}: No holders left to replicate {:?} from", self, address);
+                return Ok(NodeOperation::NoOp);
+            }
+        };
+        Ok(self
+            .get_replication_msgs(address, current_holders)
+            .await
+            .into())
+    }
+
     async fn get_replication_msgs(
         &self,
         address: BlobAddress,
@@ -530,11 +552,20 @@ impl BlobRegister {
 
     // Returns `XorName`s of the target holders for an Blob chunk.
     // Used to fetch the list of holders for a new chunk.
+    // The closest adults with space for a chunk are preferred, falling back to the closest of
+    // those known to be short of it when there aren't enough.
     async fn get_holders_for_chunk(&self, target: &XorName) -> Vec<XorName> {
-        //let closest_adults =
-        self.elder_state
-            .adults_sorted_by_distance_to(&target, CHUNK_COPY_COUNT)
+        let adults = self.elder_state.adults().await;
+        let capacity = Capacity::new(self.dbs.clone());
+        let (mut holders, short_of_space): (Vec<_>, Vec<_>) = self
+            .elder_state
+            .adults_sorted_by_distance_to(target, adults.len())
             .await
+            .into_iter()
+            .partition(|name| capacity.has_space(name, MAX_CHUNK_SIZE));
+        holders.extend(short_of_space);
+        holders.truncate(CHUNK_COPY_COUNT);
+        holders
 
         // TODO: Investigate elder blob storage
         // if closest_adults.len() < CHUNK_COPY_COUNT {
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chunk_store::{MapChunkStore, UsedSpace},
+    chunk_store::{ChunkStoreStats, MapChunkStore},
     error::convert_to_error_message,
     node::msg_wrapping::ElderMsgWrapping,
     node::node_ops::NodeMessagingDuty,
@@ -31,11 +31,24 @@ pub(super) struct MapStorage {
 
 impl MapStorage {
     pub(super) async fn new(node_info: &NodeInfo, wrapping: ElderMsgWrapping) -> Result<Self> {
-        let used_space = UsedSpace::new(node_info.max_storage_capacity);
-        let chunks = MapChunkStore::new(node_info.path(), used_space).await?;
+        let used_space = node_info.used_space.clone();
+        let chunks = MapChunkStore::new(
+            &node_info.data_dirs,
+            node_info.chunk_store_options(),
+            used_space,
+        )
+        .await?
+        .with_encryption(node_info.encryption_key.clone())
+        .await?
+        .with_quota(node_info.map_quota)
+        .await?;
         Ok(Self { chunks, wrapping })
     }
 
+    pub(super) async fn stats(&self) -> Result<ChunkStoreStats> {
+        self.chunks.stats().await
+    }
+
     pub(super) async fn read(
         &self,
         read: &MapRead,
@@ -94,13 +107,13 @@ impl MapStorage {
     /// Returns `Some(Result<..>)` if the flow should be continued, returns
     /// `None` if there was a logic error encountered and the flow should be
     /// terminated.
-    fn get_chunk(
+    async fn get_chunk(
         &self,
         address: &MapAddress,
         origin: &MsgSender,
         action: MapAction,
     ) -> Result<Map> {
-        self.chunks.get(&address).and_then(move |map| {
+        self.chunks.get(&address).await.and_then(move |map| {
             map.check_permissions(action, origin.id().public_key())
                 .map(move |_| map)
                 .map_err(|error| error.into())
@@ -118,7 +131,7 @@ impl MapStorage {
     where
         F: FnOnce(Map) -> NdResult<Map>,
     {
-        let result = match self.chunks.get(address) {
+        let result = match self.chunks.get(address).await {
             Ok(data) => match mutation_fn(data) {
                 Ok(map) => self.chunks.put(&map).await,
                 Err(error) => Err(error.into()),
@@ -136,7 +149,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -150,7 +163,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(map) => match map.check_is_owner(origin.id().public_key()) {
                 Ok(()) => {
                     info!("Deleting Map");
@@ -226,7 +239,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = match self.get_chunk(&address, origin, MapAction::Read) {
+        let result = match self.get_chunk(&address, origin, MapAction::Read).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)?),
         };
@@ -253,6 +266,7 @@ impl MapStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.shell())
         {
             Ok(res) => Ok(res),
@@ -281,6 +295,7 @@ impl MapStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.version())
         {
             Ok(res) => Ok(res),
@@ -308,7 +323,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.and_then(|data| match data {
             Map::Seq(map) => map
                 .get(key)
@@ -347,6 +362,7 @@ impl MapStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.keys())
         {
             Ok(res) => Ok(res),
@@ -373,7 +389,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.values().into(),
             Map::Unseq(map) => map.values().into(),
@@ -402,7 +418,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.entries().clone().into(),
             Map::Unseq(map) => map.entries().clone().into(),
@@ -433,6 +449,7 @@ impl MapStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.permissions())
         {
             Ok(res) => Ok(res),
@@ -461,6 +478,7 @@ impl MapStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .and_then(|data| {
                 data.user_permissions(user)
                     .map_err(|e| e.into())
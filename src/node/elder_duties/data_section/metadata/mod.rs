@@ -15,6 +15,7 @@ mod writing;
 
 use crate::{
     capacity::ChunkHolderDbs,
+    chunk_store::ChunkStoreStats,
     node::msg_wrapping::ElderMsgWrapping,
     node::node_ops::{IntoNodeOp, MetadataDuty, NodeOperation},
     node::NodeInfo,
@@ -24,6 +25,7 @@ use blob_register::BlobRegister;
 use elder_stores::ElderStores;
 use map_storage::MapStorage;
 use sequence_storage::SequenceStorage;
+use sn_data_types::BlobAddress;
 use sn_messaging::{ElderDuties, Message, MsgEnvelope};
 
 use std::fmt::{self, Display, Formatter};
@@ -58,10 +60,21 @@ impl Metadata {
         })
     }
 
+    /// The stats of the map and sequence stores.
+    pub async fn stats(&self) -> Result<(ChunkStoreStats, ChunkStoreStats)> {
+        Ok((
+            self.elder_stores.map_storage().stats().await?,
+            self.elder_stores.sequence_storage().stats().await?,
+        ))
+    }
+
     pub async fn process_metadata_duty(&mut self, duty: MetadataDuty) -> Result<NodeOperation> {
         use MetadataDuty::*;
         match duty {
             ProcessRead(msg) | ProcessWrite(msg) => self.process_msg(msg).await,
+            ProcessLostChunk { address, holder } => {
+                self.trigger_lost_chunk_replication(address, holder).await
+            }
             NoOp => Ok(NodeOperation::NoOp),
         }
     }
@@ -85,6 +98,20 @@ impl Metadata {
             .replicate_chunks(node)
             .await
     }
+
+    // This should be called whenever a holder reports that it no longer has a chunk, e.g. as it was
+    // found to be corrupt. The holder is removed from the chunk's holders, and the remaining holders
+    // are requested to store an additional copy.
+    async fn trigger_lost_chunk_replication(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeOperation> {
+        self.elder_stores
+            .blob_register_mut()
+            .replicate_lost_chunk(address, holder)
+            .await
+    }
 }
 
 impl Display for Metadata {
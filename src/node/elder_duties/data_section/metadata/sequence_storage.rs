@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chunk_store::{SequenceChunkStore, UsedSpace},
+    chunk_store::{ChunkStoreStats, SequenceChunkStore},
     error::convert_to_error_message,
     node::msg_wrapping::ElderMsgWrapping,
     node::node_ops::NodeMessagingDuty,
@@ -34,11 +34,24 @@ pub(super) struct SequenceStorage {
 
 impl SequenceStorage {
     pub(super) async fn new(node_info: &NodeInfo, wrapping: ElderMsgWrapping) -> Result<Self> {
-        let used_space = UsedSpace::new(node_info.max_storage_capacity);
-        let chunks = SequenceChunkStore::new(node_info.path(), used_space).await?;
+        let used_space = node_info.used_space.clone();
+        let chunks = SequenceChunkStore::new(
+            &node_info.data_dirs,
+            node_info.chunk_store_options(),
+            used_space,
+        )
+        .await?
+        .with_encryption(node_info.encryption_key.clone())
+        .await?
+        .with_quota(node_info.sequence_quota)
+        .await?;
         Ok(Self { chunks, wrapping })
     }
 
+    pub(super) async fn stats(&self) -> Result<ChunkStoreStats> {
+        self.chunks.stats().await
+    }
+
     pub(super) async fn read(
         &self,
         read: &SequenceRead,
@@ -91,7 +104,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -105,7 +118,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = match self.get_chunk(address, SequenceAction::Read, origin) {
+        let result = match self.get_chunk(address, SequenceAction::Read, origin).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)?),
         };
@@ -122,13 +135,13 @@ impl SequenceStorage {
             .await
     }
 
-    fn get_chunk(
+    async fn get_chunk(
         &self,
         address: SequenceAddress,
         action: SequenceAction,
         origin: &MsgSender,
     ) -> Result<Sequence> {
-        let data = self.chunks.get(&address)?;
+        let data = self.chunks.get(&address).await?;
 
         data.check_permission(action, Some(origin.id().public_key()), None)?;
         Ok(data)
@@ -140,7 +153,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: &MsgSender,
     ) -> Result<NodeMessagingDuty> {
-        let result = match self.chunks.get(&address).and_then(|sequence| {
+        let result = match self.chunks.get(&address).await.and_then(|sequence| {
             // TODO - Sequence::check_permission() doesn't support Delete yet in safe-nd
             if sequence.address().is_pub() {
                 return Err(Error::InvalidOperation);
@@ -175,6 +188,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 sequence
                     .in_range(range.0, range.1, Some(origin.id().public_key()))?
@@ -204,6 +218,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(
                 |sequence| match sequence.last_entry(Some(origin.id().public_key()))? {
                     Some(entry) => Ok((
@@ -237,6 +252,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 if sequence.is_pub() {
                     let policy = sequence.public_policy()?;
@@ -271,6 +287,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 sequence
                     .permissions(user, Some(origin.id().public_key()))
@@ -300,6 +317,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 let res = if sequence.is_pub() {
                     let policy = sequence.public_policy()?;
@@ -333,6 +351,7 @@ impl SequenceStorage {
     ) -> Result<NodeMessagingDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 let res = if !sequence.is_pub() {
                     let policy = sequence.private_policy(Some(origin.id().public_key()))?;
@@ -457,7 +476,7 @@ impl SequenceStorage {
         F: FnOnce(Sequence) -> Result<Sequence>,
     {
         info!("Getting Sequence chunk for Edit");
-        let result = self.get_chunk(address, action, origin)?;
+        let result = self.get_chunk(address, action, origin).await?;
         let sequence = write_fn(result)?;
         info!("Edited Sequence chunk successfully");
         self.chunks.put(&sequence).await
@@ -17,7 +17,7 @@ use crate::{
     capacity::ChunkHolderDbs,
     node::node_ops::{DataSectionDuty, NodeOperation, RewardCmd, RewardDuty},
     node::NodeInfo,
-    ElderState, Result,
+    ElderState, Result, StorageStats,
 };
 use log::info;
 use sn_data_types::{OwnerType, Result as DtResult, Signing, WalletInfo};
@@ -136,6 +136,17 @@ impl DataSection {
         }
     }
 
+    /// The stats of the map and sequence stores, and of the space used by the node.
+    pub async fn stats(&self) -> Result<StorageStats> {
+        let (maps, sequences) = self.metadata.stats().await?;
+        Ok(StorageStats {
+            blobs: None,
+            maps: Some(maps),
+            sequences: Some(sequences),
+            used_space: self.elder_state.info().used_space.stats().await,
+        })
+    }
+
     /// Issues query to Elders of the section
     /// as to catch up with the current state of the replicas.
     pub async fn catchup_with_section(&mut self) -> Result<NodeOperation> {
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::Age;
+use crate::economics;
 use sn_data_types::Token;
 use sn_routing::Prefix;
 
@@ -29,11 +30,7 @@ impl RewardCalc {
     }
 
     fn reward_from(age: Age, prefix_len: usize) -> Token {
-        let time = 2_u64.pow(age as u32);
-        let nanos = 1_000_000_000;
-        let network_size = 2_u64.pow(prefix_len as u32);
-        let steepness_reductor = prefix_len as u64 + 1;
-        Token::from_nano(time * nanos / network_size * steepness_reductor)
+        economics::reward(age, prefix_len)
     }
 }
 
@@ -59,4 +56,15 @@ mod test {
         assert!(reward >= Token::from_nano(1));
         Ok(())
     }
+
+    #[test]
+    fn rewards_beyond_the_nanos_do_not_overflow() -> Result<()> {
+        assert_eq!(Token::zero(), RewardCalc::reward_from(5, 64));
+        assert_eq!(Token::zero(), RewardCalc::reward_from(5, 256));
+        assert_eq!(
+            Token::from_nano(u64::MAX),
+            RewardCalc::reward_from(Age::MAX, 0)
+        );
+        Ok(())
+    }
 }
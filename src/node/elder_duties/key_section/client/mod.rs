@@ -9,6 +9,9 @@
 mod client_input_parse;
 mod client_msg_handling;
 mod onboarding;
+mod throttling;
+
+pub use self::throttling::{ThrottleStats, Throttling};
 
 use self::{
     client_input_parse::{try_deserialize_handshake, try_deserialize_msg},
@@ -16,35 +19,58 @@ use self::{
     onboarding::Onboarding,
 };
 use crate::{
-    node::node_ops::{GatewayDuty, KeySectionDuty, NodeMessagingDuty, NodeOperation},
+    error::convert_to_error_message,
+    node::{
+        msg_wrapping::ElderMsgWrapping,
+        node_ops::{GatewayDuty, KeySectionDuty, NodeMessagingDuty, NodeOperation},
+    },
     ElderState, Error, Result,
 };
-use log::{error, trace, warn};
+use log::{error, info, trace, warn};
 use sn_data_types::Error as DtError;
-use sn_messaging::{Address, MsgEnvelope};
+use sn_messaging::{Address, Cmd, ElderDuties, Message, MessageId, MsgEnvelope};
 use sn_routing::Event as RoutingEvent;
-use std::fmt::{self, Display, Formatter};
+use std::{
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    sync::Arc,
+};
 
 /// A client gateway routes messages
 /// back and forth between a client and the network.
 pub struct ClientGateway {
     client_msg_handling: ClientMsgHandling,
+    throttling: Arc<Throttling>,
+    wrapping: ElderMsgWrapping,
     elder_state: ElderState,
 }
 
 impl ClientGateway {
-    pub async fn new(elder_state: ElderState) -> Result<Self> {
+    pub async fn new(elder_state: ElderState, throttling: Arc<Throttling>) -> Result<Self> {
         let onboarding = Onboarding::new(elder_state.clone());
         let client_msg_handling = ClientMsgHandling::new(onboarding);
+        let wrapping = ElderMsgWrapping::new(elder_state.clone(), ElderDuties::Gateway);
 
         let gateway = Self {
             client_msg_handling,
+            throttling,
+            wrapping,
             elder_state,
         };
 
         Ok(gateway)
     }
 
+    /// The counters of the client requests let through and throttled so far.
+    pub fn throttle_stats(&self) -> ThrottleStats {
+        self.throttling.stats()
+    }
+
+    /// Drops the throttling state of the clients which haven't sent requests lately.
+    pub fn prune_throttling(&self) {
+        self.throttling.prune()
+    }
+
     pub async fn process_as_gateway(&self, cmd: GatewayDuty) -> Result<NodeOperation> {
         trace!("Processing as gateway");
         use GatewayDuty::*;
@@ -100,7 +126,7 @@ impl ClientGateway {
                             .track_incoming_message(&msg.message, src)
                             .await
                         {
-                            Ok(()) => Ok(KeySectionDuty::EvaluateClientMsg(msg).into()),
+                            Ok(()) => self.throttle(msg, src).await,
                             Err(e) => Err(e),
                         }
                     }
@@ -114,6 +140,61 @@ impl ClientGateway {
             }
         }
     }
+
+    /// Passes the msg on for evaluation, unless the client has sent too many requests, in which
+    /// case it is responded to with an error. Paid data writes are throttled too, but don't take
+    /// from the allowance of the client: only those whose payment is refused do, once refused.
+    async fn throttle(&self, msg: MsgEnvelope, src: SocketAddr) -> Result<NodeOperation> {
+        let client = msg.origin.id().public_key();
+        let check = match &msg.message {
+            Message::Cmd {
+                cmd: Cmd::Data { .. },
+                ..
+            } => self.throttling.check_paid(client, src),
+            _ => self.throttling.check(client, src),
+        };
+        let retry_after = match check {
+            Ok(()) => return Ok(KeySectionDuty::EvaluateClientMsg(msg).into()),
+            Err(retry_after) => retry_after,
+        };
+        info!(
+            "Throttling msg {:?} from client {:?} at {}, retry after {:?}",
+            msg.id(),
+            client,
+            src,
+            retry_after
+        );
+        let error = convert_to_error_message(Error::Throttled { retry_after })?;
+        let message = match &msg.message {
+            Message::Query { query, id } => Message::QueryResponse {
+                response: query.error(error),
+                id: MessageId::in_response_to(id),
+                correlation_id: *id,
+                query_origin: msg.origin.address(),
+            },
+            Message::Cmd {
+                cmd: Cmd::Transfer(cmd),
+                id,
+            } => Message::CmdError {
+                error: cmd.error(error),
+                id: MessageId::in_response_to(id),
+                correlation_id: *id,
+                cmd_origin: msg.origin.address(),
+            },
+            Message::Cmd {
+                cmd: Cmd::Data { cmd, .. },
+                id,
+            } => Message::CmdError {
+                error: cmd.error(error),
+                id: MessageId::in_response_to(id),
+                correlation_id: *id,
+                cmd_origin: msg.origin.address(),
+            },
+            // Other msgs aren't responded to, so they are just dropped.
+            _ => return Ok(NodeOperation::NoOp),
+        };
+        Ok(self.wrapping.send_to_client(message).await?.into())
+    }
 }
 
 fn validate_client_sig(msg: &MsgEnvelope) -> bool {
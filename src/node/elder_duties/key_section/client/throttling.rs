@@ -0,0 +1,283 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use dashmap::DashMap;
+use sn_data_types::PublicKey;
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Counters of the client requests let through and throttled by an Elder.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ThrottleStats {
+    /// Number of requests let through.
+    pub allowed: u64,
+    /// Number of requests throttled.
+    pub throttled: u64,
+    /// Number of client keys currently tracked.
+    pub tracked_keys: usize,
+    /// Number of client socket addresses currently tracked.
+    pub tracked_addrs: usize,
+}
+
+/// Token bucket throttling of the unpaid requests (i.e. queries and transfers) of clients, per
+/// client key and per socket address.  Each bucket holds up to `burst` requests, and refills at
+/// `rate` requests per second.  Refused payments are taken from the bucket of the client key too,
+/// and paid writes are let through while neither bucket is empty.
+pub struct Throttling {
+    rate: f64,
+    burst: f64,
+    by_key: DashMap<PublicKey, Bucket>,
+    by_addr: DashMap<SocketAddr, Bucket>,
+    allowed: AtomicU64,
+    throttled: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Throttling {
+    /// Throttling to `rate` requests per second, in bursts of up to `burst`.  A `rate` of 0
+    /// disables it.
+    pub fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            rate: rate as f64,
+            burst: burst.max(1) as f64,
+            by_key: DashMap::new(),
+            by_addr: DashMap::new(),
+            allowed: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// Lets a request of the client `key` at `addr` through, or returns how long it is to wait
+    /// before retrying.
+    pub fn check(&self, key: PublicKey, addr: SocketAddr) -> Result<(), Duration> {
+        self.check_at(key, addr, Instant::now())
+    }
+
+    /// Lets a paid write of the client `key` at `addr` through unless either bucket is empty,
+    /// without taking from them, as its payment is taken from that of the key if refused.
+    pub fn check_paid(&self, key: PublicKey, addr: SocketAddr) -> Result<(), Duration> {
+        self.take_at(key, addr, 0.0, Instant::now())
+    }
+
+    fn check_at(&self, key: PublicKey, addr: SocketAddr, now: Instant) -> Result<(), Duration> {
+        self.take_at(key, addr, 1.0, now)
+    }
+
+    /// Takes `cost` requests from the buckets of `key` and `addr` if both hold a request.
+    fn take_at(
+        &self,
+        key: PublicKey,
+        addr: SocketAddr,
+        cost: f64,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if self.rate <= 0.0 {
+            return Ok(());
+        }
+        let mut key_bucket = self
+            .by_key
+            .entry(key)
+            .or_insert_with(|| self.new_bucket(now));
+        let mut addr_bucket = self
+            .by_addr
+            .entry(addr)
+            .or_insert_with(|| self.new_bucket(now));
+        self.refill(&mut key_bucket, now);
+        self.refill(&mut addr_bucket, now);
+        match (
+            self.retry_after(&key_bucket),
+            self.retry_after(&addr_bucket),
+        ) {
+            (None, None) => {
+                key_bucket.tokens -= cost;
+                addr_bucket.tokens -= cost;
+                let _ = self.allowed.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            (key_wait, addr_wait) => {
+                let _ = self.throttled.fetch_add(1, Ordering::Relaxed);
+                Err(key_wait.max(addr_wait).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Takes a request from the bucket of the client `key`, if it holds any, e.g. for a payment
+    /// refused as too low.
+    pub fn charge(&self, key: PublicKey) {
+        self.charge_at(key, Instant::now())
+    }
+
+    fn charge_at(&self, key: PublicKey, now: Instant) {
+        if self.rate <= 0.0 {
+            return;
+        }
+        let mut bucket = self
+            .by_key
+            .entry(key)
+            .or_insert_with(|| self.new_bucket(now));
+        self.refill(&mut bucket, now);
+        bucket.tokens = (bucket.tokens - 1.0).max(0.0);
+    }
+
+    /// Drops the buckets which have refilled, as they are the same as new ones.  Run
+    /// periodically, to keep only those of the clients which sent requests lately.
+    pub fn prune(&self) {
+        self.prune_at(Instant::now())
+    }
+
+    fn prune_at(&self, now: Instant) {
+        self.by_key
+            .retain(|_, bucket| !self.is_refilled(bucket, now));
+        self.by_addr
+            .retain(|_, bucket| !self.is_refilled(bucket, now));
+    }
+
+    /// The counters of the requests let through and throttled so far.
+    pub fn stats(&self) -> ThrottleStats {
+        ThrottleStats {
+            allowed: self.allowed.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            tracked_keys: self.by_key.len(),
+            tracked_addrs: self.by_addr.len(),
+        }
+    }
+
+    fn new_bucket(&self, now: Instant) -> Bucket {
+        Bucket {
+            tokens: self.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+    }
+
+    fn is_refilled(&self, bucket: &Bucket, now: Instant) -> bool {
+        let mut bucket = *bucket;
+        self.refill(&mut bucket, now);
+        bucket.tokens >= self.burst
+    }
+
+    /// How long until `bucket` holds a request, if it doesn't.
+    fn retry_after(&self, bucket: &Bucket) -> Option<Duration> {
+        if bucket.tokens >= 1.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls::SecretKey;
+
+    #[test]
+    fn requests_are_throttled_per_key_and_addr() {
+        let throttling = Throttling::new(2, 3);
+        let key = PublicKey::Bls(SecretKey::random().public_key());
+        let other_key = PublicKey::Bls(SecretKey::random().public_key());
+        let addr: SocketAddr = ([127, 0, 0, 1], 1000).into();
+        let other_addr: SocketAddr = ([127, 0, 0, 2], 1000).into();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(Ok(()), throttling.check_at(key, addr, start));
+        }
+        // Half a second for the next request, at 2 per second.
+        assert_eq!(
+            Err(Duration::from_millis(500)),
+            throttling.check_at(key, addr, start)
+        );
+        // Same key from elsewhere, or same address with another key.
+        assert!(throttling.check_at(key, other_addr, start).is_err());
+        assert!(throttling.check_at(other_key, addr, start).is_err());
+        assert_eq!(Ok(()), throttling.check_at(other_key, other_addr, start));
+
+        let later = start + Duration::from_millis(500);
+        assert_eq!(Ok(()), throttling.check_at(key, addr, later));
+        assert!(throttling.check_at(key, addr, later).is_err());
+
+        let stats = throttling.stats();
+        assert_eq!(5, stats.allowed);
+        assert_eq!(4, stats.throttled);
+        assert_eq!(2, stats.tracked_keys);
+        assert_eq!(2, stats.tracked_addrs);
+    }
+
+    #[test]
+    fn charged_requests_are_throttled() {
+        let throttling = Throttling::new(1, 2);
+        let key = PublicKey::Bls(SecretKey::random().public_key());
+        let addr: SocketAddr = ([127, 0, 0, 1], 1000).into();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            throttling.charge_at(key, start);
+        }
+        assert_eq!(
+            Err(Duration::from_secs(1)),
+            throttling.check_at(key, addr, start)
+        );
+        let later = start + Duration::from_secs(1);
+        assert_eq!(Ok(()), throttling.check_at(key, addr, later));
+    }
+
+    #[test]
+    fn paid_writes_are_only_let_through_while_allowed() {
+        let throttling = Throttling::new(1, 2);
+        let key = PublicKey::Bls(SecretKey::random().public_key());
+        let addr: SocketAddr = ([127, 0, 0, 1], 1000).into();
+        let start = Instant::now();
+
+        // Paid writes don't take from the buckets.
+        for _ in 0..3 {
+            assert_eq!(Ok(()), throttling.take_at(key, addr, 0.0, start));
+        }
+        assert_eq!(Ok(()), throttling.check_at(key, addr, start));
+        assert_eq!(Ok(()), throttling.check_at(key, addr, start));
+        assert!(throttling.take_at(key, addr, 0.0, start).is_err());
+    }
+
+    #[test]
+    fn refilled_buckets_are_pruned() {
+        let throttling = Throttling::new(1, 2);
+        let key = PublicKey::Bls(SecretKey::random().public_key());
+        let addr: SocketAddr = ([127, 0, 0, 1], 1000).into();
+        let start = Instant::now();
+
+        assert_eq!(Ok(()), throttling.check_at(key, addr, start));
+        throttling.prune_at(start);
+        assert_eq!(1, throttling.stats().tracked_keys);
+        throttling.prune_at(start + Duration::from_secs(1));
+        assert_eq!(0, throttling.stats().tracked_keys);
+        assert_eq!(0, throttling.stats().tracked_addrs);
+    }
+
+    #[test]
+    fn zero_rate_disables_throttling() {
+        let throttling = Throttling::new(0, 1);
+        let key = PublicKey::Bls(SecretKey::random().public_key());
+        let addr: SocketAddr = ([127, 0, 0, 1], 1000).into();
+        for _ in 0..10 {
+            assert_eq!(Ok(()), throttling.check(key, addr));
+        }
+    }
+}
@@ -10,8 +10,10 @@ mod client;
 mod client_msg_analysis;
 mod transfers;
 
+pub use self::client::ThrottleStats;
+
 use self::{
-    client::ClientGateway,
+    client::{ClientGateway, Throttling},
     client_msg_analysis::ClientMsgAnalysis,
     transfers::{replica_signing::ReplicaSigning, replicas::Replicas, Transfers},
 };
@@ -23,7 +25,9 @@ use crate::{
 use log::{info, trace};
 use sn_data_types::{PublicKey, TransferPropagated};
 use sn_routing::Prefix;
+use std::sync::Arc;
 use transfers::replica_signing::ReplicaSigningImpl;
+use xor_name::XorName;
 
 #[derive(Clone, Debug)]
 ///
@@ -56,9 +60,14 @@ pub struct KeySection {
 
 impl KeySection {
     pub async fn new(rate_limit: RateLimit, elder_state: ElderState) -> Result<Self> {
-        let gateway = ClientGateway::new(elder_state.clone()).await?;
+        let info = elder_state.info();
+        let throttling = Arc::new(Throttling::new(
+            info.client_request_rate,
+            info.client_request_burst,
+        ));
+        let gateway = ClientGateway::new(elder_state.clone(), throttling.clone()).await?;
         let replicas = Self::transfer_replicas(elder_state.clone())?;
-        let transfers = Transfers::new(elder_state.clone(), replicas, rate_limit);
+        let transfers = Transfers::new(elder_state.clone(), replicas, rate_limit, throttling);
         let msg_analysis = ClientMsgAnalysis::new(elder_state.clone());
 
         Ok(Self {
@@ -69,9 +78,24 @@ impl KeySection {
         })
     }
 
-    ///
-    pub async fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<()> {
-        self.transfers.increase_full_node_count(node_id)
+    /// The counters of the client requests let through and throttled by the gateway.
+    pub fn throttle_stats(&self) -> ThrottleStats {
+        self.gateway.throttle_stats()
+    }
+
+    /// Drops the throttling state of the clients which haven't sent requests lately.
+    pub fn prune_throttling(&self) {
+        self.gateway.prune_throttling()
+    }
+
+    /// Records that the adult `node_id` has reported being full.
+    pub async fn set_node_full(&mut self, node_id: PublicKey) -> Result<()> {
+        self.transfers.set_node_full(node_id)
+    }
+
+    /// Stops counting the adult `name` as full, as it has left the section.
+    pub async fn remove_node(&mut self, name: XorName) -> Result<()> {
+        self.transfers.remove_node(name)
     }
 
     /// Initiates as first node in a network.
@@ -98,7 +122,11 @@ impl KeySection {
     }
 
     // Update our replica with the latest keys
-    pub fn elders_changed(&mut self, elder_state: ElderState, rate_limit: RateLimit) {
+    pub async fn elders_changed(
+        &mut self,
+        elder_state: ElderState,
+        rate_limit: RateLimit,
+    ) -> Result<()> {
         // TODO: Query sn_routing for info for [new_section_key]
         // specifically (regardless of how far back that was) - i.e. not the current info!
         let id = elder_state.public_key_share();
@@ -113,7 +141,9 @@ impl KeySection {
             signing,
             initiating: false,
         };
-        self.transfers.update_replica_info(info, rate_limit);
+        self.transfers
+            .update_replica_info(elder_state, info, rate_limit)
+            .await
     }
 
     /// When section splits, the Replicas in either resulting section
@@ -135,6 +165,7 @@ impl KeySection {
 
     fn transfer_replicas(elder_state: ElderState) -> Result<Replicas<ReplicaSigningImpl>> {
         let root_dir = elder_state.info().root_dir.clone();
+        let encryption_key = elder_state.info().encryption_key.clone();
         let id = elder_state.public_key_share();
         let key_index = elder_state.key_index();
         let peer_replicas = elder_state.public_key_set().clone();
@@ -147,7 +178,7 @@ impl KeySection {
             signing,
             initiating: true,
         };
-        let replica_manager = Replicas::new(root_dir, info)?;
+        let replica_manager = Replicas::new(root_dir, encryption_key, info)?;
         Ok(replica_manager)
     }
 }
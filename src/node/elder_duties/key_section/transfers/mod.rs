@@ -13,7 +13,7 @@ pub mod store;
 mod test_utils;
 
 use self::replicas::Replicas;
-use super::ReplicaInfo;
+use super::{client::Throttling, ReplicaInfo};
 use crate::{
     capacity::RateLimit,
     error::{convert_dt_error_to_error_message, convert_to_error_message},
@@ -38,8 +38,11 @@ use sn_messaging::{
     MsgEnvelope, NodeCmd, NodeCmdError, NodeEvent, NodeQuery, NodeQueryResponse, NodeTransferCmd,
     NodeTransferError, NodeTransferQuery, NodeTransferQueryResponse, QueryResponse, TransferError,
 };
-use std::fmt::{self, Display, Formatter};
-use xor_name::Prefix;
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+use xor_name::{Prefix, XorName};
 
 /*
 Transfers is the layer that manages
@@ -70,8 +73,10 @@ Replicas don't initiate transfers or drive the algo - only Actors do.
 /// Transfers is the layer that manages
 /// interaction with an AT2 Replica.
 pub struct Transfers {
+    elder_state: ElderState,
     replicas: Replicas<ReplicaSigningImpl>,
     rate_limit: RateLimit,
+    throttling: Arc<Throttling>,
     wrapping: ElderMsgWrapping,
 }
 
@@ -80,11 +85,14 @@ impl Transfers {
         elder_state: ElderState,
         replicas: Replicas<ReplicaSigningImpl>,
         rate_limit: RateLimit,
+        throttling: Arc<Throttling>,
     ) -> Self {
-        let wrapping = ElderMsgWrapping::new(elder_state, ElderDuties::Transfer);
+        let wrapping = ElderMsgWrapping::new(elder_state.clone(), ElderDuties::Transfer);
         Self {
+            elder_state,
             replicas,
             rate_limit,
+            throttling,
             wrapping,
         }
     }
@@ -125,8 +133,13 @@ impl Transfers {
     }
 
     ///
-    pub fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<()> {
-        self.rate_limit.increase_full_node_count(node_id)
+    pub fn set_node_full(&mut self, node_id: PublicKey) -> Result<()> {
+        self.rate_limit.set_node_full(node_id)
+    }
+
+    ///
+    pub fn remove_node(&mut self, name: XorName) -> Result<()> {
+        self.rate_limit.remove_node(name)
     }
 
     /// When handled by Elders in the dst
@@ -221,13 +234,18 @@ impl Transfers {
     }
 
     ///
-    pub fn update_replica_info(
+    pub async fn update_replica_info(
         &mut self,
+        elder_state: ElderState,
         info: ReplicaInfo<ReplicaSigningImpl>,
         rate_limit: RateLimit,
-    ) {
+    ) -> Result<()> {
+        self.elder_state = elder_state;
+        let mut rate_limit = rate_limit;
+        rate_limit.keep_quotes_of(&self.rate_limit);
         self.rate_limit = rate_limit;
         self.replicas.update_replica_info(info);
+        self.rate_limit.retain_current_nodes().await
     }
 
     /// Initiates a new Replica with the
@@ -241,6 +259,9 @@ impl Transfers {
     /// Makes sure the payment contained
     /// within a data write, is credited
     /// to the section funds.
+    /// The payment is checked against the store cost quoted
+    /// in the current or previous quote period, before it
+    /// is registered, so that too low a payment is not forfeited.
     async fn process_payment(&self, msg: &MsgEnvelope) -> Result<NodeMessagingDuty> {
         let (payment, num_bytes) = match &msg.message {
             Message::Cmd {
@@ -268,6 +289,30 @@ impl Transfers {
                 )
                 .await;
         }
+
+        // Paying too little is refused, before any of it is
+        // registered, so nothing is forfeited. As refused writes
+        // thus cost nothing, they are counted towards the
+        // throttle of the sender's key instead, like unpaid
+        // requests, so that they can't be used to spam the network.
+        let total_cost = self.rate_limit.lowest_honoured(num_bytes).await;
+        if total_cost > payment.amount() {
+            let error = Error::TooLowPayment {
+                paid: payment.amount(),
+                expected: total_cost,
+            };
+            warn!("Payment: {}", error);
+            self.throttling.charge(payment.sender());
+            return self
+                .wrapping
+                .error(
+                    CmdError::Transfer(TransferRegistration(convert_to_error_message(error)?)),
+                    msg.id(),
+                    &msg.origin.address(),
+                )
+                .await;
+        }
+
         let registration = self.replicas.register(&payment).await;
         let result = match registration {
             Ok(_) => match self
@@ -283,27 +328,6 @@ impl Transfers {
         match result {
             Ok(_) => {
                 info!("Payment: registration and propagation succeeded.");
-                // Paying too little will see the amount be forfeited.
-                // This prevents spam of the network.
-                let total_cost = self.rate_limit.from(num_bytes).await;
-                if total_cost > payment.amount() {
-                    warn!(
-                        "Payment: Too low payment: {}, expected: {}",
-                        payment.amount(),
-                        total_cost
-                    );
-                    // todo, better error, like `TooLowPayment`
-                    return self
-                        .wrapping
-                        .error(
-                            CmdError::Transfer(TransferRegistration(
-                                ErrorMessage::InsufficientBalance,
-                            )),
-                            msg.id(),
-                            &msg.origin.address(),
-                        )
-                        .await;
-                }
                 info!("Payment: forwarding data..");
                 // consider having the section actor be
                 // informed of this transfer as well..
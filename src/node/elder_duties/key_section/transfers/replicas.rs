@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{replica_signing::ReplicaSigning, store::TransferStore, ReplicaInfo};
-use crate::{Error, Result};
+use crate::{encryption::EncryptionKey, Error, Result};
 use bls::PublicKeySet;
 use dashmap::DashMap;
 use futures::lock::Mutex;
@@ -39,15 +39,21 @@ where
     T: ReplicaSigning,
 {
     root_dir: PathBuf,
+    encryption_key: Option<EncryptionKey>,
     info: ReplicaInfo<T>,
     locks: WalletLocks,
     self_lock: Arc<Mutex<usize>>,
 }
 
 impl<T: ReplicaSigning> Replicas<T> {
-    pub(crate) fn new(root_dir: PathBuf, info: ReplicaInfo<T>) -> Result<Self> {
+    pub(crate) fn new(
+        root_dir: PathBuf,
+        encryption_key: Option<EncryptionKey>,
+        info: ReplicaInfo<T>,
+    ) -> Result<Self> {
         Ok(Self {
             root_dir,
+            encryption_key,
             info,
             locks: Default::default(),
             self_lock: Arc::new(Mutex::new(0)),
@@ -64,7 +70,9 @@ impl<T: ReplicaSigning> Replicas<T> {
             .locks
             .iter()
             .map(|r| *r.key())
-            .filter_map(|id| TransferStore::new(id.into(), &self.root_dir).ok())
+            .filter_map(|id| {
+                TransferStore::new(id.into(), &self.root_dir, self.encryption_key.clone()).ok()
+            })
             .map(|store| store.get_all())
             .flatten()
             .collect();
@@ -73,7 +81,7 @@ impl<T: ReplicaSigning> Replicas<T> {
 
     /// History of actor
     pub async fn history(&self, id: PublicKey) -> Result<ActorHistory> {
-        let store = TransferStore::new(id.into(), &self.root_dir);
+        let store = TransferStore::new(id.into(), &self.root_dir, self.encryption_key.clone());
 
         if let Err(error) = store {
             // hmm.. can we handle this in a better way?
@@ -136,7 +144,8 @@ impl<T: ReplicaSigning> Replicas<T> {
     ///
     pub async fn balance(&self, id: PublicKey) -> Result<Token> {
         debug!("Replica: Getting balance of: {:?}", id);
-        let store = match TransferStore::new(id.into(), &self.root_dir) {
+        let store = match TransferStore::new(id.into(), &self.root_dir, self.encryption_key.clone())
+        {
             Ok(store) => store,
             // store load failed, so we return 0 balance
             Err(_) => return Ok(Token::from_nano(0)),
@@ -287,7 +296,11 @@ impl<T: ReplicaSigning> Replicas<T> {
                     Ok(store) => store,
                     Err(_) => {
                         // no key lock (hence no store), so we create one
-                        let store = TransferStore::new(id.into(), &self.root_dir)?;
+                        let store = TransferStore::new(
+                            id.into(),
+                            &self.root_dir,
+                            self.encryption_key.clone(),
+                        )?;
                         let locked_store = Arc::new(Mutex::new(store));
                         let _ = self.locks.insert(id, locked_store.clone());
                         let _ = self_lock.overflowing_add(0); // resolve: is a usage at end of block necessary to actually engage the lock?
@@ -373,10 +386,16 @@ impl<T: ReplicaSigning> Replicas<T> {
         let key_lock = match self.load_key_lock(id).await {
             Ok(lock) => lock,
             Err(_) => {
-                let store = match TransferStore::new(id.into(), &self.root_dir) {
+                let store = match TransferStore::new(
+                    id.into(),
+                    &self.root_dir,
+                    self.encryption_key.clone(),
+                ) {
                     Ok(store) => store,
                     // no key lock, so we create one for this payout...
-                    Err(_e) => TransferStore::new(id.into(), &self.root_dir)?,
+                    Err(_e) => {
+                        TransferStore::new(id.into(), &self.root_dir, self.encryption_key.clone())?
+                    }
                 };
                 debug!("store retrieved..");
                 let locked_store = Arc::new(Mutex::new(store));
@@ -415,7 +434,7 @@ impl<T: ReplicaSigning> Replicas<T> {
             return Err(Error::BalanceExists);
         }
         // No key lock (hence no store), so we create one
-        let store = TransferStore::new(id.into(), &self.root_dir)?;
+        let store = TransferStore::new(id.into(), &self.root_dir, self.encryption_key.clone())?;
         let locked_store = Arc::new(Mutex::new(store));
         let _ = self.locks.insert(id, locked_store.clone());
         // Acquire lock of the wallet.
@@ -755,7 +774,7 @@ mod test {
             initiating: true,
         };
         let root_dir = temp_dir()?;
-        let replicas = Replicas::new(root_dir.path().to_path_buf(), info)?;
+        let replicas = Replicas::new(root_dir.path().to_path_buf(), None, info)?;
 
         let keypair =
             Keypair::new_bls_share(0, bls_secret_key.secret_key_share(0), peer_replicas.clone());
@@ -6,9 +6,11 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Error, Result, ToDbKey};
+use crate::{
+    encryption::{Db, EncryptionKey},
+    utils, Error, Result, ToDbKey,
+};
 use log::{debug, trace};
-use pickledb::PickleDb;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     fmt::Debug,
@@ -23,7 +25,7 @@ const DB_EXTENSION: &str = ".db";
 /// Disk storage for transfers.
 pub struct TransferStore<TEvent: Debug + Serialize + DeserializeOwned> {
     id: XorName,
-    db: PickleDb,
+    db: Db,
     _phantom: PhantomData<TEvent>,
 }
 
@@ -31,12 +33,20 @@ impl<'a, TEvent: Debug + Serialize + DeserializeOwned> TransferStore<TEvent>
 where
     TEvent: 'a,
 {
-    pub fn new(id: XorName, root_dir: &PathBuf) -> Result<Self> {
+    /// Opens the store for `id`, encrypting the events in it with `encryption_key`, if any.
+    pub fn new(
+        id: XorName,
+        root_dir: &PathBuf,
+        encryption_key: Option<EncryptionKey>,
+    ) -> Result<Self> {
         let db_dir = root_dir.join(Path::new(TRANSFERS_DIR_NAME));
         let db_name = format!("{}{}", id.to_db_key()?, DB_EXTENSION);
+        let db = utils::new_auto_dump_db(db_dir.as_path(), db_name)?;
+        let mut db = Db::new(db, encryption_key);
+        db.migrate::<TEvent>()?;
         Ok(Self {
             id,
-            db: utils::new_auto_dump_db(db_dir.as_path(), db_name)?,
+            db,
             _phantom: PhantomData::default(),
         })
     }
@@ -87,7 +97,7 @@ where
                 key, event
             )));
         }
-        self.db.set(key, &event).map_err(Error::PickleDb)
+        self.db.set(key, &event)
     }
 }
 
@@ -103,10 +113,10 @@ mod test {
 
     #[test]
     fn history() -> Result<()> {
-        let id = xor_name::XorName::random();
+        let id = XorName::random();
         let tmp_dir = TempDir::new("root")?;
         let root_dir = tmp_dir.into_path();
-        let mut store = TransferStore::new(id, &root_dir)?;
+        let mut store = TransferStore::new(id, &root_dir, None)?;
         let wallet_id = get_random_pk();
         let mut rng = rand::thread_rng();
         let bls_secret_key = SecretKeySet::random(0, &mut rng);
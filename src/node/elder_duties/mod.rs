@@ -9,11 +9,13 @@
 mod data_section;
 mod key_section;
 
+pub use self::key_section::ThrottleStats;
+
 use self::{data_section::DataSection, key_section::KeySection};
 use crate::{
     capacity::{Capacity, ChunkHolderDbs, RateLimit},
     node::node_ops::{ElderDuty, NodeOperation},
-    ElderState, Result,
+    ElderState, Result, StorageStats,
 };
 use log::trace;
 use sn_data_types::{PublicKey, TransferPropagated, WalletInfo};
@@ -26,19 +28,21 @@ pub struct ElderDuties {
     state: ElderState,
     key_section: KeySection,
     data_section: DataSection,
+    dbs: ChunkHolderDbs,
 }
 
 impl ElderDuties {
     pub async fn new(wallet_info: WalletInfo, state: ElderState) -> Result<Self> {
         let info = state.info();
-        let dbs = ChunkHolderDbs::new(info.path())?;
+        let dbs = ChunkHolderDbs::new(info.path(), info.encryption_key.clone())?;
         let rate_limit = RateLimit::new(state.clone(), Capacity::new(dbs.clone()));
         let key_section = KeySection::new(rate_limit, state.clone()).await?;
-        let data_section = DataSection::new(info, dbs, wallet_info, state.clone()).await?;
+        let data_section = DataSection::new(info, dbs.clone(), wallet_info, state.clone()).await?;
         Ok(Self {
             state,
             key_section,
             data_section,
+            dbs,
         })
     }
 
@@ -47,6 +51,21 @@ impl ElderDuties {
         &self.state
     }
 
+    /// The stats of the map and sequence stores, and of the space used by the node.
+    pub async fn stats(&self) -> Result<StorageStats> {
+        self.data_section.stats().await
+    }
+
+    /// The counters of the client requests let through and throttled.
+    pub fn throttle_stats(&self) -> ThrottleStats {
+        self.key_section.throttle_stats()
+    }
+
+    /// Drops the throttling state of the clients which haven't sent requests lately.
+    pub fn prune_throttling(&self) {
+        self.key_section.prune_throttling()
+    }
+
     /// Issues queries to Elders of the section
     /// as to catch up with shares state and
     /// start working properly in the group.
@@ -85,7 +104,7 @@ impl ElderDuties {
                     .await
             }
             RunAsDataSection(duty) => self.data_section.process_data_section_duty(duty).await,
-            StorageFull { node_id } => self.increase_full_node_count(node_id).await,
+            StorageFull { node_id } => self.set_node_full(node_id).await,
             SwitchNodeJoin(joins_allowed) => {
                 self.key_section.set_node_join_flag(joins_allowed).await
             }
@@ -98,9 +117,9 @@ impl ElderDuties {
         self.data_section.new_node_joined(name).await
     }
 
-    async fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<NodeOperation> {
+    async fn set_node_full(&mut self, node_id: PublicKey) -> Result<NodeOperation> {
         self.key_section
-            .increase_full_node_count(node_id)
+            .set_node_full(node_id)
             .await
             .map(|()| NodeOperation::NoOp)
     }
@@ -119,6 +138,7 @@ impl ElderDuties {
 
     ///
     async fn member_left(&mut self, node_id: XorName, age: u8) -> Result<NodeOperation> {
+        self.key_section.remove_node(node_id).await?;
         self.data_section.member_left(node_id, age).await
     }
 
@@ -134,11 +154,9 @@ impl ElderDuties {
     ///
     pub async fn finish_elder_change(&mut self, state: ElderState) -> Result<()> {
         // 2. Then we must update key section..
-        let info = state.info();
-        let dbs = ChunkHolderDbs::new(info.path())?;
-        let rate_limit = RateLimit::new(state.clone(), Capacity::new(dbs));
-        self.key_section.elders_changed(state, rate_limit);
-        Ok(())
+        // The same dbs as the data section's, so that both see the same full adults.
+        let rate_limit = RateLimit::new(state.clone(), Capacity::new(self.dbs.clone()));
+        self.key_section.elders_changed(state, rate_limit).await
     }
 
     ///
@@ -0,0 +1,210 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Versioning of the on-disk format of the root dir (the chunk stores, used space records, state
+//! files and databases) and of the data dirs (the chunk stores and used space records), and the
+//! migrations between versions.
+
+use crate::{Config, Error, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+use tokio::fs;
+
+/// File within the root dir, and each data dir, recording the version of its format.
+const FORMAT_FILENAME: &str = "format.json";
+
+/// Version of the on-disk format written by this node.  Bump it, and add a step to `MIGRATIONS`,
+/// with any change to what's stored in the root dir that older nodes can't read or newer nodes
+/// would misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The steps migrating a root or data dir to each version from the previous one, in order.
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "Start versioning the root and data dirs",
+    // Dirs predating versioning need no changes: their chunk stores are migrated to the
+    // configured layout, and their used space records rebuilt if need be, when opened.
+    run: |_| Ok(()),
+}];
+
+/// A step migrating the contents of a root or data dir from the previous version to `version`.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    run: fn(&Path) -> Result<()>,
+}
+
+/// The record of the format version of a root or data dir.
+#[derive(Debug, Deserialize, Serialize)]
+struct FormatManifest {
+    version: u32,
+    /// Version of the node that last wrote the manifest.
+    node_version: String,
+}
+
+/// Brings the root dir and the data dirs of the node configured by `config` up to
+/// `FORMAT_VERSION`, each on its own, running the migration steps from its current version in
+/// order.  New dirs, including those that don't exist yet, are simply marked as being of the
+/// current version.
+///
+/// Fails with `Error::UnsupportedFormatVersion` if a dir was written by a newer node.
+pub async fn migrate_dirs(config: &Config) -> Result<()> {
+    let mut dirs: BTreeSet<_> = config
+        .data_dirs()
+        .iter()
+        .map(|data_dir| data_dir.path.clone())
+        .collect();
+    let _ = dirs.insert(config.root_dir()?);
+    for dir in dirs {
+        fs::create_dir_all(&dir).await?;
+        migrate_with(&dir, FORMAT_VERSION, MIGRATIONS).await?;
+    }
+    Ok(())
+}
+
+async fn migrate_with(root_dir: &Path, current: u32, migrations: &[Migration]) -> Result<()> {
+    let version = match read_version(root_dir).await? {
+        Some(version) => version,
+        None if is_empty(root_dir).await? => current,
+        // Written before versioning.
+        None => 0,
+    };
+    if version > current {
+        return Err(Error::UnsupportedFormatVersion(version, current));
+    }
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > version && migration.version <= current)
+    {
+        info!(
+            "Migrating {} to format version {}: {}",
+            root_dir.display(),
+            migration.version,
+            migration.description
+        );
+        (migration.run)(root_dir)?;
+        // Recorded after each step, so that an interrupted migration resumes where it stopped.
+        write_version(root_dir, migration.version).await?;
+    }
+    write_version(root_dir, current).await
+}
+
+async fn read_version(root_dir: &Path) -> Result<Option<u32>> {
+    let path = root_dir.join(FORMAT_FILENAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let manifest: FormatManifest = serde_json::from_slice(&fs::read(path).await?)?;
+    Ok(Some(manifest.version))
+}
+
+async fn write_version(root_dir: &Path, version: u32) -> Result<()> {
+    let manifest = FormatManifest {
+        version,
+        node_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let path = root_dir.join(FORMAT_FILENAME);
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&manifest)?).await?;
+    fs::rename(temp_path, path).await?;
+    Ok(())
+}
+
+async fn is_empty(dir: &Path) -> Result<bool> {
+    let mut entries = fs::read_dir(dir).await?;
+    Ok(entries.next_entry().await?.is_none())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ConfigSource;
+    use tempdir::TempDir;
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "first",
+            run: |root_dir| Ok(std::fs::write(root_dir.join("first"), b"1")?),
+        },
+        Migration {
+            version: 2,
+            description: "second",
+            run: |root_dir| {
+                // Relies on the first step having run.
+                let _ = std::fs::read(root_dir.join("first"))?;
+                Ok(std::fs::write(root_dir.join("second"), b"2")?)
+            },
+        },
+    ];
+
+    #[tokio::test]
+    async fn migrations_run_in_order_once() -> Result<()> {
+        let root_dir = TempDir::new("migrations")?;
+        std::fs::write(root_dir.path().join("age_group"), b"")?;
+
+        migrate_with(root_dir.path(), 1, TEST_MIGRATIONS).await?;
+        assert!(root_dir.path().join("first").is_file());
+        assert!(!root_dir.path().join("second").is_file());
+        assert_eq!(Some(1), read_version(root_dir.path()).await?);
+
+        // Already run steps aren't run again.
+        std::fs::write(root_dir.path().join("first"), b"")?;
+        migrate_with(root_dir.path(), 2, TEST_MIGRATIONS).await?;
+        assert_eq!(b"".to_vec(), std::fs::read(root_dir.path().join("first"))?);
+        assert!(root_dir.path().join("second").is_file());
+        assert_eq!(Some(2), read_version(root_dir.path()).await?);
+
+        // Refuses to downgrade.
+        assert!(matches!(
+            migrate_with(root_dir.path(), 1, TEST_MIGRATIONS).await,
+            Err(Error::UnsupportedFormatVersion(2, 1))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn root_and_data_dirs_are_versioned() -> Result<()> {
+        let root_dir = TempDir::new("migrations")?;
+        let data_dir = TempDir::new("migrations")?;
+        let new_data_dir = data_dir.path().join("new");
+        std::fs::write(data_dir.path().join("immutable"), b"")?;
+        let mut config = Config::default();
+        config.set_root_dir(root_dir.path());
+        for dir in &[data_dir.path(), &new_data_dir] {
+            config.set_value(
+                "data-dir",
+                &format!("{}=1024", dir.display()),
+                ConfigSource::CommandLine,
+            )?;
+        }
+
+        migrate_dirs(&config).await?;
+        for dir in &[root_dir.path(), data_dir.path(), &new_data_dir] {
+            assert_eq!(Some(FORMAT_VERSION), read_version(dir).await?);
+        }
+
+        // A data dir written by a newer node is refused.
+        write_version(&new_data_dir, FORMAT_VERSION + 1).await?;
+        assert!(matches!(
+            migrate_dirs(&config).await,
+            Err(Error::UnsupportedFormatVersion(..))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn new_root_dir_is_not_migrated() -> Result<()> {
+        let root_dir = TempDir::new("migrations")?;
+        migrate_with(root_dir.path(), 2, TEST_MIGRATIONS).await?;
+        assert!(!root_dir.path().join("first").is_file());
+        assert_eq!(Some(2), read_version(root_dir.path()).await?);
+        Ok(())
+    }
+}
@@ -8,33 +8,63 @@
 
 mod adult_duties;
 mod elder_duties;
+mod migrations;
 mod msg_wrapping;
 mod node_duties;
 mod node_ops;
 pub mod state_db;
 
+pub use self::{elder_duties::ThrottleStats, migrations::migrate_dirs};
+
 use crate::{
+    chunk_store::{UsedSpace, USED_SPACE_FLUSH_INTERVAL},
     node::{
         node_duties::NodeDuties,
-        node_ops::{GatewayDuty, NetworkDuty, NodeDuty, NodeOperation},
-        state_db::{get_age_group, store_age_group, store_new_reward_keypair, AgeGroup},
+        node_ops::{AdultDuty, ChunkStoreDuty, GatewayDuty, NetworkDuty, NodeDuty, NodeOperation},
+        state_db::{
+            get_age_group, get_encryption_key, store_age_group, store_new_encryption_key,
+            store_new_reward_keypair, AgeGroup,
+        },
     },
-    Config, Error, Network, NodeInfo, Result,
+    utils, Config, Error, Network, NodeInfo, Result, StorageStats,
 };
 use bls::SecretKey;
-use log::{error, info};
+use log::{error, info, warn};
 use sn_data_types::PublicKey;
 use sn_routing::{Event, EventStream, MIN_AGE};
 use std::{
     fmt::{self, Display, Formatter},
+    fs,
     net::SocketAddr,
+    path::Path,
+    time::{Duration, SystemTime},
 };
+use tokio::time;
+
+/// How often an Adult checks whether its storage is full, so that it keeps reporting being full
+/// while idle, and stops once it has space again (e.g. once its max capacity is raised).
+const STORAGE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the config file is checked for changes, to apply them without a restart.  A fresh
+/// node has no config file, so its config isn't reloaded.
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often an Elder drops the throttling state of the clients which haven't sent requests
+/// lately.
+const THROTTLING_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Main node struct.
 pub struct Node {
     duties: NodeDuties,
     network_api: Network,
     network_events: EventStream,
+    /// Number of stored chunks per minute checked for corruption, when running as an Adult.
+    scrub_rate: u64,
+    /// The config last read, which changes are applied from.
+    config: Config,
+    /// When the config file was last modified, if it exists.
+    config_modified: Option<SystemTime>,
+    used_space: UsedSpace,
 }
 
 impl Node {
@@ -42,7 +72,7 @@ impl Node {
     pub async fn new(config: &Config) -> Result<Self> {
         let root_dir_buf = config.root_dir()?;
         let root_dir = root_dir_buf.as_path();
-        std::fs::create_dir_all(root_dir)?;
+        migrate_dirs(config).await?;
 
         let reward_key_task = async move {
             let res: Result<PublicKey>;
@@ -74,14 +104,39 @@ impl Node {
         let (reward_key, _age_group) = tokio::try_join!(reward_key_task, age_group_task)?;
         let (network_api, network_events) = Network::new(config).await?;
 
+        // Once enabled, encryption stays enabled, as the existing data is encrypted.
+        let encryption_key = match get_encryption_key(root_dir).await? {
+            Some(encryption_key) => Some(encryption_key),
+            None if config.encrypt_at_rest() => Some(store_new_encryption_key(root_dir).await?),
+            None => None,
+        };
+
+        let used_space = UsedSpace::new(config.max_capacity());
+        for data_dir in config.data_dirs() {
+            used_space
+                .add_volume(&data_dir.path, data_dir.capacity)
+                .await;
+        }
+
         let node_info = NodeInfo {
             genesis: config.is_first(),
             node_id: PublicKey::Ed25519(network_api.public_key().await),
             root_dir: root_dir_buf,
+            data_dirs: config.chunk_dirs()?,
             /// Upper limit in bytes for allowed network storage on this node.
             /// An Adult would be using the space for chunks,
             /// while an Elder uses it for metadata.
-            max_storage_capacity: config.max_capacity(),
+            used_space,
+            blob_quota: config.blob_quota(),
+            map_quota: config.map_quota(),
+            sequence_quota: config.sequence_quota(),
+            chunk_dir_depth: config.chunk_dir_depth(),
+            chunk_backend: config.chunk_store_backend(),
+            chunk_cache_size: config.chunk_cache_size(),
+            pricing: config.pricing(),
+            client_request_rate: config.client_request_rate(),
+            client_request_burst: config.client_request_burst(),
+            encryption_key,
             reward_key,
         };
 
@@ -98,20 +153,17 @@ impl Node {
             Infant
         };
 
+        let used_space = node_info.used_space.clone();
         let mut duties = NodeDuties::new(node_info, network_api.clone()).await;
         let next_duty = match age_group {
             Infant => Ok(NodeOperation::NoOp),
             Adult => {
                 info!("Starting as Adult");
-                duties
-                    .process_node_duty(node_ops::NodeDuty::AssumeAdultDuties)
-                    .await
+                duties.process_node_duty(NodeDuty::AssumeAdultDuties).await
             }
             Elder => {
                 info!("Starting as Elder");
-                duties
-                    .process_node_duty(node_ops::NodeDuty::AssumeElderDuties)
-                    .await
+                duties.process_node_duty(NodeDuty::AssumeElderDuties).await
             }
         };
 
@@ -119,6 +171,10 @@ impl Node {
             duties,
             network_api,
             network_events,
+            scrub_rate: config.scrub_rate(),
+            config: config.clone(),
+            config_modified: modified(config.config_file()),
+            used_space,
         };
 
         node.process_while_any(next_duty).await;
@@ -139,24 +195,171 @@ impl Node {
         self.network_api.is_elder().await
     }
 
+    /// The stats of the stores of the node, as an Adult or Elder, or `None` if it is neither yet.
+    pub async fn storage_stats(&mut self) -> Result<Option<StorageStats>> {
+        if let Some(duties) = self.duties.adult_duties() {
+            return duties.stats().await.map(Some);
+        }
+        match self.duties.elder_duties() {
+            Some(duties) => duties.stats().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The counters of the client requests let through and throttled, or `None` if the node is not
+    /// an Elder.
+    pub fn throttle_stats(&mut self) -> Option<ThrottleStats> {
+        self.duties
+            .elder_duties()
+            .map(|duties| duties.throttle_stats())
+    }
+
     /// Starts the node, and runs the main event loop.
-    /// Blocks until the node is terminated, which is done
-    /// by client sending in a `Command` to free it.
+    /// Blocks until the node is terminated, by SIGINT or SIGTERM,
+    /// and flushes the used space records before returning.
     pub async fn run(&mut self) -> Result<()> {
         let info = self.network_api.our_connection_info().await?;
         info!("Listening for routing events at: {}", info);
-        while let Some(event) = self.network_events.next().await {
-            info!("New event received from the Network: {:?}", event);
-            let duty = if let Event::ClientMessageReceived { .. } = event {
-                info!("Event from client peer: {:?}", event);
-                GatewayDuty::ProcessClientEvent(event).into()
-            } else {
-                NodeDuty::ProcessNetworkEvent(event).into()
-            };
-            self.process_while_any(Ok(duty)).await;
+        let (scrub_period, scrub_batch) = scrub_schedule(self.scrub_rate);
+        let mut scrub_timer = time::interval(scrub_period);
+        let mut storage_check_timer = time::interval(STORAGE_CHECK_INTERVAL);
+        let mut config_check_timer = time::interval(CONFIG_CHECK_INTERVAL);
+        let reload_config = self.config.config_file().is_some();
+        if !reload_config {
+            info!("Not reloading the config on changes, as no config file is read");
+        }
+        let mut used_space_flush_timer = time::interval(USED_SPACE_FLUSH_INTERVAL);
+        let mut throttling_prune_timer = time::interval(THROTTLING_PRUNE_INTERVAL);
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                event = self.network_events.next() => {
+                    let event = match event {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    info!("New event received from the Network: {:?}", event);
+                    let duty = if let Event::ClientMessageReceived { .. } = event {
+                        info!("Event from client peer: {:?}", event);
+                        GatewayDuty::ProcessClientEvent(event).into()
+                    } else {
+                        NodeDuty::ProcessNetworkEvent(event).into()
+                    };
+                    self.process_while_any(Ok(duty)).await;
+                }
+                _ = scrub_timer.tick(), if self.scrub_rate > 0 => {
+                    if self.duties.adult_duties().is_some() {
+                        let duty =
+                            AdultDuty::RunAsChunkStore(ChunkStoreDuty::ScrubChunks(scrub_batch));
+                        self.process_while_any(Ok(duty.into())).await;
+                    }
+                }
+                _ = storage_check_timer.tick() => {
+                    if self.duties.adult_duties().is_some() {
+                        let duty = AdultDuty::RunAsChunkStore(ChunkStoreDuty::CheckStorage);
+                        self.process_while_any(Ok(duty.into())).await;
+                    }
+                }
+                _ = config_check_timer.tick(), if reload_config => {
+                    let config_modified = modified(self.config.config_file());
+                    if config_modified != self.config_modified {
+                        self.config_modified = config_modified;
+                        self.reload_config().await;
+                    }
+                }
+                _ = used_space_flush_timer.tick() => {
+                    if let Err(e) = self.used_space.flush().await {
+                        warn!("Could not flush the used space records: {}", e);
+                    }
+                }
+                _ = throttling_prune_timer.tick() => {
+                    if let Some(duties) = self.duties.elder_duties() {
+                        duties.prune_throttling();
+                    }
+                }
+                _ = &mut shutdown => {
+                    info!("Shutting down");
+                    break;
+                }
+            }
+        }
+
+        self.used_space.flush().await
+    }
+
+    /// Reads the config again, and applies the changes which can be without a restart: to the max
+    /// capacity, the log level and the reward wallet. Other changes are logged and ignored, so
+    /// they are logged again on the next reload, until the node is restarted.
+    async fn reload_config(&mut self) {
+        let config = match Config::reload() {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Not applying the changed config, as it is invalid: {}", e);
+                return;
+            }
+        };
+        let changes = match self.config.changes(&config) {
+            Ok(changes) => changes,
+            Err(e) => {
+                error!("Failed to compare the changed config: {}", e);
+                return;
+            }
+        };
+        for key in changes {
+            match key {
+                "max-capacity" => {
+                    self.config.apply(key, &config);
+                    // Without a max capacity set, it is that of the data dirs we are running with.
+                    let max_capacity = self.config.max_capacity();
+                    info!("Changing max capacity to {}", max_capacity);
+                    self.used_space.set_max_capacity(max_capacity).await;
+                }
+                "verbose" => {
+                    info!("Changing log level to {}", config.verbose());
+                    match utils::set_log_level(config.verbose()) {
+                        Ok(()) => self.config.apply(key, &config),
+                        Err(e) => warn!("Not changing the log level: {}", e),
+                    }
+                }
+                "wallet-id" => {
+                    if self.change_wallet(config.wallet_id()).await {
+                        self.config.apply(key, &config);
+                    }
+                }
+                key => warn!(
+                    "Not applying the change of {} from {}, as it needs a restart",
+                    key,
+                    config.source(key)
+                ),
+            }
         }
+    }
 
-        Ok(())
+    /// Changes the wallet rewards are sent to, registering it with the section if a member.
+    /// Returns whether it was changed.
+    async fn change_wallet(&mut self, wallet_id: Option<&String>) -> bool {
+        let wallet_id = match wallet_id {
+            Some(wallet_id) => wallet_id,
+            None => {
+                warn!("Not unsetting the wallet, as a new one would have to be generated");
+                return false;
+            }
+        };
+        let reward_key = match state_db::pk_from_hex(wallet_id) {
+            Ok(public_key) => PublicKey::Bls(public_key),
+            Err(e) => {
+                warn!("Not changing the wallet to {}: {}", wallet_id, e);
+                return false;
+            }
+        };
+        info!("Changing wallet to {}", reward_key);
+        self.duties.set_reward_key(reward_key);
+        if self.duties.adult_duties().is_some() || self.duties.elder_duties().is_some() {
+            self.process_while_any(Ok(NodeDuty::RegisterWallet(reward_key).into()))
+                .await;
+        }
+        true
     }
 
     /// Keeps processing resulting node operations.
@@ -220,6 +423,51 @@ impl Node {
     }
 }
 
+/// When the file at `path` was last modified, if it exists.
+fn modified(path: Option<&Path>) -> Option<SystemTime> {
+    path.and_then(|path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    })
+}
+
+/// Completes once the process is asked to terminate, by SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => (),
+                    _ = terminate.recv() => (),
+                }
+                return;
+            }
+            Err(e) => warn!("Could not listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Could not listen for SIGINT: {}", e);
+        futures::future::pending::<()>().await
+    }
+}
+
+/// Splits a rate of chunks per minute into the period between scrubbing rounds, and the number of
+/// chunks checked per round.  Rounds are at most once a second.
+fn scrub_schedule(rate: u64) -> (Duration, usize) {
+    const SECS_PER_MINUTE: u64 = 60;
+    if rate == 0 {
+        (Duration::from_secs(SECS_PER_MINUTE), 0)
+    } else if rate <= SECS_PER_MINUTE {
+        (Duration::from_secs(SECS_PER_MINUTE / rate), 1)
+    } else {
+        let batch = (rate - 1) / SECS_PER_MINUTE + 1;
+        (Duration::from_secs(1), batch as usize)
+    }
+}
+
 impl Display for Node {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter, "Node")
@@ -163,6 +163,11 @@ impl NodeDuties {
         }
     }
 
+    /// Changes the key the node receives its rewards at, e.g. as the wallet has been reconfigured.
+    pub fn set_reward_key(&mut self, reward_key: PublicKey) {
+        self.node_info.reward_key = reward_key;
+    }
+
     pub fn adult_duties(&mut self) -> Option<&mut AdultDuties> {
         use Stage::*;
         match &mut self.stage {
@@ -248,6 +253,7 @@ impl NodeDuties {
         }
     }
 
+    /// Tells the Elders of our section that our storage is full.
     async fn notify_section_of_our_storage(&mut self) -> Result<NodeOperation> {
         let adult_state = match self.adult_duties() {
             Some(duties) => duties.state().clone(),
@@ -17,10 +17,11 @@ use crate::{
 };
 use log::{debug, error, info, trace};
 use sn_messaging::{
-    Address, AdultDuties::ChunkStorage, Cmd, DataQuery, Duty, ElderDuties, Message, MessageId,
-    MsgEnvelope, NodeCmd, NodeDataCmd, NodeDataQuery, NodeDataQueryResponse, NodeDuties, NodeEvent,
-    NodeQuery, NodeQueryResponse, NodeRewardQuery, NodeRewardQueryResponse, NodeSystemCmd,
-    NodeTransferCmd, NodeTransferQuery, NodeTransferQueryResponse, Query,
+    Address, AdultDuties::ChunkStorage, Cmd, DataQuery, Duty, ElderDuties, Error as ErrorMessage,
+    Message, MessageId, MsgEnvelope, NodeCmd, NodeCmdError, NodeDataCmd, NodeDataError,
+    NodeDataQuery, NodeDataQueryResponse, NodeDuties, NodeEvent, NodeQuery, NodeQueryResponse,
+    NodeRewardQuery, NodeRewardQueryResponse, NodeSystemCmd, NodeTransferCmd, NodeTransferQuery,
+    NodeTransferQueryResponse, Query,
 };
 
 use sn_routing::MIN_AGE;
@@ -293,6 +294,26 @@ impl NetworkMsgAnalysis {
 
         let is_correct_dst = self.is_elder().await;
 
+        let from_chunk_holder = || sender.is_adult() && matches!(duty, Duty::Adult(ChunkStorage));
+        // Holders report the chunks they have lost as failed replications of them.
+        if let Message::NodeCmdError {
+            error:
+                NodeCmdError::Data(NodeDataError::ChunkReplication {
+                    address,
+                    error: ErrorMessage::NoSuchData,
+                }),
+            ..
+        } = &msg.message
+        {
+            if from_chunk_holder() && is_correct_dst {
+                return Ok(MetadataDuty::ProcessLostChunk {
+                    address: *address,
+                    holder: msg.origin.address().xorname(),
+                });
+            }
+            return Ok(MetadataDuty::NoOp);
+        }
+
         let duty = if is_data_query() && from_single_gateway_elder() && is_correct_dst {
             MetadataDuty::ProcessRead(msg.clone()) // TODO: Fix these for type safety
         } else if is_data_cmd() && from_transfer_section() && is_correct_dst {
@@ -412,6 +412,15 @@ pub enum MetadataDuty {
     ProcessRead(MsgEnvelope),
     /// Writes.
     ProcessWrite(MsgEnvelope),
+    /// A holder reported that it no longer
+    /// has a chunk (e.g. since it was corrupt),
+    /// so it is to be replicated from the remaining holders.
+    ProcessLostChunk {
+        /// The address of the chunk.
+        address: BlobAddress,
+        /// The holder that lost it.
+        holder: XorName,
+    },
     NoOp,
 }
 
@@ -438,6 +447,10 @@ pub enum ChunkStoreDuty {
     ReadChunk(MsgEnvelope),
     /// Writes.
     WriteChunk(MsgEnvelope),
+    /// Re-checks the given number of stored chunks for corruption.
+    ScrubChunks(usize),
+    /// Checks whether our storage is full, reporting to our section if that changed.
+    CheckStorage,
     NoOp,
 }
 
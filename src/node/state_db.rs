@@ -6,15 +6,23 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Error, Result};
+use crate::{
+    encryption::{EncryptionKey, KEY_SIZE},
+    utils, Error, Result,
+};
 use bls::{self, serde_impl::SerdeSecret, PublicKey, SecretKey, PK_SIZE};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use tokio::fs;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+use tokio::{fs, task};
 
 const AGE_GROUP_FILENAME: &str = "age_group";
 const REWARD_PUBLIC_KEY_FILENAME: &str = "reward_public_key";
 const REWARD_SECRET_KEY_FILENAME: &str = "reward_secret_key";
+const ENCRYPTION_KEY_FILENAME: &str = "encryption_secret_key";
 
 /// Writes the public and secret key to different locations at disk.
 pub async fn store_new_reward_keypair(
@@ -29,6 +37,63 @@ pub async fn store_new_reward_keypair(
     Ok(())
 }
 
+/// Generates a new key to encrypt data at rest with, and writes it to disk, readable by the owner
+/// only.
+pub async fn store_new_encryption_key(root_dir: &Path) -> Result<EncryptionKey> {
+    let key = EncryptionKey::random();
+    let path = root_dir.join(ENCRYPTION_KEY_FILENAME);
+    let contents = vec_to_hex(key.to_bytes().to_vec());
+    task::spawn_blocking(move || write_secret(&path, contents.as_bytes()))
+        .await
+        .map_err(io::Error::from)??;
+    Ok(key)
+}
+
+/// Writes `contents` to a temp file readable by the owner only, syncs it, and then renames it into
+/// place at `path`, so that a crash never leaves a partially written secret behind.
+fn write_secret(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    // Left behind by an interrupted write, and possibly with other permissions.
+    if temp_path.exists() {
+        std::fs::remove_file(&temp_path)?;
+    }
+    let mut options = OpenOptions::new();
+    let _ = options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let _ = options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    std::fs::rename(&temp_path, path)?;
+    // Persist the rename itself. Directories can't be opened for syncing on other platforms.
+    match path.parent() {
+        Some(parent) if cfg!(unix) => File::open(parent)?.sync_all(),
+        _ => Ok(()),
+    }
+}
+
+/// Returns Some(EncryptionKey) or None if file doesn't exist.
+pub async fn get_encryption_key(root_dir: &Path) -> Result<Option<EncryptionKey>> {
+    let path = root_dir.join(ENCRYPTION_KEY_FILENAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).await?;
+    let key_bytes = parse_hex(&contents);
+    if key_bytes.len() != KEY_SIZE {
+        return Err(Error::Logic(
+            "Config error: Invalid encryption key bytes".to_string(),
+        ));
+    }
+    let mut key_bytes_array = [0; KEY_SIZE];
+    key_bytes_array.copy_from_slice(&key_bytes);
+    Ok(Some(EncryptionKey::from_bytes(key_bytes_array)))
+}
+
 /// Writes the info to disk.
 pub async fn store_age_group(root_dir: &Path, age_group: &AgeGroup) -> Result<()> {
     let path = root_dir.join(AGE_GROUP_FILENAME);
@@ -117,4 +182,24 @@ mod test {
     fn gen_key() -> PublicKey {
         SecretKey::random().public_key()
     }
+
+    #[tokio::test]
+    async fn encryption_key_is_written_for_the_owner_only() -> Result<()> {
+        let root_dir = tempdir::TempDir::new("state_db")?;
+        // Left behind by an interrupted write.
+        std::fs::write(root_dir.path().join("encryption_secret_key.tmp"), b"")?;
+
+        let key = store_new_encryption_key(root_dir.path()).await?;
+        let read = get_encryption_key(root_dir.path()).await?;
+        assert_eq!(Some(key.to_bytes()), read.map(|key| key.to_bytes()));
+        assert!(!root_dir.path().join("encryption_secret_key.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = root_dir.path().join(ENCRYPTION_KEY_FILENAME);
+            let mode = std::fs::metadata(path)?.permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        Ok(())
+    }
 }
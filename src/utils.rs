@@ -10,17 +10,23 @@
 
 use crate::{config_handler::Config, Error, Result};
 use bytes::Bytes;
-use flexi_logger::{DeferredNow, Logger};
+use flexi_logger::{DeferredNow, Logger, ReconfigurationHandle};
+use lazy_static::lazy_static;
 use log::debug;
-use log::{Log, Metadata, Record};
+use log::{Level, Log, Metadata, Record};
 use pickledb::{PickleDb, PickleDbDumpPolicy};
 use rand::{distributions::Standard, CryptoRng, Rng};
 use serde::{de::DeserializeOwned, Serialize};
 use std::io::Write;
-use std::{fs, path::Path};
+use std::{fs, path::Path, sync::Mutex};
 
 const NODE_MODULE_NAME: &str = "sn_node";
 
+lazy_static! {
+    /// Handle to the logger started by `init_logging`, to change its level.
+    static ref LOG_HANDLE: Mutex<Option<ReconfigurationHandle>> = Mutex::new(None);
+}
+
 pub(crate) fn new_auto_dump_db<D: AsRef<Path>, N: AsRef<Path>>(
     db_dir: D,
     db_name: N,
@@ -75,9 +81,7 @@ pub fn init_logging(config: &Config) {
         )
     };
 
-    let level_filter = config.verbose().to_level_filter();
-    let module_log_filter = format!("{}={}", NODE_MODULE_NAME, level_filter.to_string());
-    let logger = Logger::with_env_or_str(module_log_filter)
+    let logger = Logger::with_env_or_str(module_log_filter(config.verbose()))
         .format(do_format)
         .suppress_timestamp();
 
@@ -87,15 +91,40 @@ pub fn init_logging(config: &Config) {
         logger
     };
 
-    if let Ok((logger, _)) = logger.build() {
+    if let Ok((logger, handle)) = logger.build() {
         let logger = LoggerWrapper(logger);
 
         async_log::Logger::wrap(logger, || 5433)
             .start(config.verbose().to_level_filter())
             .unwrap_or(());
+        if let Ok(mut log_handle) = LOG_HANDLE.lock() {
+            *log_handle = Some(handle);
+        }
     }
 }
 
+/// Changes the level of the logs of the node, once started by `init_logging`.
+pub(crate) fn set_log_level(level: Level) -> Result<()> {
+    let mut log_handle = LOG_HANDLE
+        .lock()
+        .map_err(|_| Error::Logic("Log handle lock is poisoned".to_string()))?;
+    match log_handle.as_mut() {
+        Some(handle) => {
+            handle.parse_new_spec(&module_log_filter(level));
+            Ok(())
+        }
+        None => Err(Error::Logic("Logging is not initialised".to_string())),
+    }
+}
+
+fn module_log_filter(level: Level) -> String {
+    format!(
+        "{}={}",
+        NODE_MODULE_NAME,
+        level.to_level_filter().to_string()
+    )
+}
+
 struct LoggerWrapper(Box<dyn Log>);
 
 impl Log for LoggerWrapper {